@@ -1,8 +1,26 @@
 // Handles the CLI parser, calls the required functions and also handles any errors
 
+use chrono::NaiveDate;
 use clap::{ArgGroup, Args, Parser, Subcommand};
-use std::io;
-use todo::{self, messages, TaskStatus};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+use todo::csv::{Column, DEFAULT_COLUMNS};
+use todo::journal::Journal;
+use todo::lists::{TaskLists, DEFAULT_LIST};
+use todo::output::{self, print_value, records_with_ids, task_records, OutputFormat};
+use todo::search::Matcher;
+use todo::views::Views;
+use todo::{
+    self, clock,
+    dates::{DueFilter, TimeFormat},
+    formats::Format,
+    messages,
+    tags::TagFilter,
+    Destination, ListOptions, Priority, Selection, SortKey, TaskStatus,
+};
 
 #[derive(Parser)]
 #[clap(author, version, long_about = None)]
@@ -18,22 +36,47 @@ pub struct Cli {
     /// Mark a task as complete
     ///
     /// A pattern like 1-5,8,10-12 (without spaces)
-    /// can also be used to mark multiple tasks at once
-    #[clap(long = "mark-done", short = 'x', value_name = "TASK(S)")]
+    /// can also be used to mark multiple tasks at once,
+    /// subtasks can be selected like 3.2 (or 3.2-4)
+    /// and tasks can also be selected by their ID like #42,
+    /// by all, done, open, last, -2 (the second to last task),
+    /// 5- (from task 5 to the last task) or /regex/ matching their text,
+    /// and tasks starting with ! are left out like 1-10,!4
+    #[clap(
+        long = "mark-done",
+        short = 'x',
+        value_name = "TASK(S)",
+        allow_hyphen_values = true
+    )]
     pub mark: Option<String>,
 
     /// Unmark a completed task back to incomplete
     ///
     /// A pattern like 1-5,8,10-12 (without spaces)
-    /// can also be used to mark multiple tasks at once
-    #[clap(long = "unmark", short = 'u', value_name = "TASK(S)")]
+    /// can also be used to mark multiple tasks at once,
+    /// subtasks can be selected like 3.2 (or 3.2-4)
+    /// and tasks can also be selected by their ID like #42,
+    /// by all, done, open, last, -2 (the second to last task),
+    /// 5- (from task 5 to the last task) or /regex/ matching their text,
+    /// and tasks starting with ! are left out like 1-10,!4
+    #[clap(
+        long = "unmark",
+        short = 'u',
+        value_name = "TASK(S)",
+        allow_hyphen_values = true
+    )]
     pub unmark: Option<String>,
 
     /// Remove a specific task
     ///
     /// A pattern like 1-5,8,10-12 (without spaces)
-    /// can also be used to remove multiple tasks at once
-    #[clap(long, short, value_name = "TASK(S)")]
+    /// can also be used to remove multiple tasks at once,
+    /// subtasks can be selected like 3.2 (or 3.2-4)
+    /// and tasks can also be selected by their ID like #42,
+    /// by all, done, open, last, -2 (the second to last task),
+    /// 5- (from task 5 to the last task) or /regex/ matching their text,
+    /// and tasks starting with ! are left out like 1-10,!4
+    #[clap(long, short, value_name = "TASK(S)", allow_hyphen_values = true)]
     pub remove: Option<String>,
 
     /// Remove all tasks marked as complete
@@ -43,6 +86,50 @@ pub struct Cli {
     /// Delete all tasks
     #[clap(long, short = 'D', action, value_parser)]
     pub delete_all: bool,
+
+    /// Also mark all subtasks as complete when marking tasks having incomplete subtasks
+    ///
+    /// Without this, tasks having incomplete subtasks are left unchanged
+    #[clap(long, short, action, value_parser, requires = "mark")]
+    pub cascade: bool,
+
+    /// Use the list with this name instead of the default list
+    #[clap(long, short, global = true, value_name = "NAME")]
+    pub list: Option<String>,
+
+    /// Write the output as text, json or ndjson (JSON with a value on every line)
+    ///
+    /// Listing writes the tasks with all of their fields and their index like 3.2,
+    /// other commands write which tasks they changed and which tasks they ignored
+    #[clap(
+        long,
+        short,
+        global = true,
+        value_name = "FORMAT",
+        default_value = "text"
+    )]
+    pub output: OutputFormat,
+
+    /// Fail without changing the tasks when a selector has tasks which aren't in the list
+    ///
+    /// Without this, such parts of the selector (and tasks selected more than once) are
+    /// only reported as warnings
+    #[clap(long, global = true, action)]
+    pub strict: bool,
+
+    /// Also show when tasks were added and completed, like "added 3d ago"
+    ///
+    /// The times are relative by default, --verbose=absolute shows them like 2023-09-20 09:30
+    #[clap(
+        long,
+        short,
+        global = true,
+        value_name = "relative|absolute",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "relative"
+    )]
+    pub verbose: Option<TimeFormat>,
 }
 
 #[derive(Subcommand)]
@@ -50,11 +137,444 @@ enum Commands {
     /// Add new items
     #[clap(visible_alias = "a")]
     Add(Add),
+
+    /// List all tasks
+    #[clap(visible_alias = "l")]
+    List(List),
+
+    /// Change the priority of tasks
+    #[clap(visible_alias = "p")]
+    Priority(SetPriority),
+
+    /// Change the due date of tasks
+    #[clap(visible_alias = "d")]
+    Due(SetDue),
+
+    /// Add or remove tags of tasks
+    #[clap(visible_alias = "t")]
+    Tag(SetTags),
+
+    /// List all tags with the number of open and done tasks
+    Tags,
+
+    /// List the tasks of a view defined in the views file
+    ///
+    /// The views file has lines like "today = open due:<=today sort:priority", and views
+    /// can also be used to select tasks like @today
+    View {
+        /// Name of the view
+        name: String,
+    },
+
+    /// Show all views with their queries
+    Views,
+
+    /// Find the tasks whose text has the query, which are shown with their numbers
+    #[clap(visible_alias = "s")]
+    Search(Search),
+
+    /// Show all lists or create, rename, delete lists and move tasks between them
+    Lists(Lists),
+
+    /// Change the text of a task, or edit the whole list in $EDITOR when no task is given
+    #[clap(visible_alias = "e")]
+    Edit(Edit),
+
+    /// Move tasks to another position in the list
+    #[clap(visible_alias = "m")]
+    Move(Move),
+
+    /// Undo the last changes to the tasks
+    Undo(Steps),
+
+    /// Redo the last undone changes to the tasks
+    Redo(Steps),
+
+    /// Show the recent changes to the tasks which can be undone
+    History,
+
+    /// Add the tasks from a file in another format to the list
+    Import(Import),
+
+    /// Write the tasks of the list to a file in another format
+    Export(Export),
 }
 
 #[derive(Args)]
 struct Add {
+    /// Tasks to add, any +tag in them is added as a tag
     pub items: Vec<String>,
+
+    /// Priority of the added tasks (high, medium or low)
+    #[clap(long, short)]
+    pub priority: Option<Priority>,
+
+    /// Due date of the added tasks
+    ///
+    /// Either a date like 2023-10-20 or a relative date like
+    /// today, tomorrow, fri, +3d, +2w, +1m or "next week"
+    #[clap(long, short, value_name = "DATE")]
+    pub due: Option<String>,
+
+    /// Add the tasks as subtasks of this task (like 3, 3.2 or #42)
+    #[clap(long, value_name = "TASK")]
+    pub parent: Option<String>,
+}
+
+#[derive(Args)]
+struct List {
+    /// Show the tasks with the highest priority first (the same as --sort priority)
+    #[clap(long, short = 'p', action, value_parser)]
+    pub by_priority: bool,
+
+    /// Only show tasks which aren't done
+    #[clap(long, action, value_parser, conflicts_with = "done")]
+    pub open: bool,
+
+    /// Only show tasks which are done
+    #[clap(long, action, value_parser)]
+    pub done: bool,
+
+    /// Sort the tasks by text, status, created, due or priority
+    ///
+    /// Tasks which are equal keep their order, and tasks are still shown
+    /// with their numbers in the list so that they can be selected by them
+    #[clap(long, short, value_name = "KEY", conflicts_with = "by_priority")]
+    pub sort: Option<SortKey>,
+
+    /// Show the tasks in the opposite order
+    #[clap(long, short, action, value_parser)]
+    pub reverse: bool,
+
+    /// Show at most this many tasks
+    #[clap(long, short = 'n', value_name = "N")]
+    pub limit: Option<usize>,
+
+    /// Only show tasks which are overdue, due today or due this week
+    #[clap(long, short, value_name = "overdue|today|week")]
+    pub due: Option<DueFilter>,
+
+    /// Only show tasks added today, this week or on dates compared like ">=-7d" or "<2023-10-01"
+    #[clap(long, value_name = "FILTER", value_parser = parse_date_filter, allow_hyphen_values = true)]
+    pub created: Option<DueFilter>,
+
+    /// Only show tasks completed today, this week or on dates compared like ">=mon"
+    #[clap(long, value_name = "FILTER", value_parser = parse_date_filter, allow_hyphen_values = true)]
+    pub completed: Option<DueFilter>,
+
+    /// Show the tasks of every list
+    #[clap(long, short, action, value_parser)]
+    pub all: bool,
+
+    /// Only show tasks with tags like +work and without tags like -personal
+    /// (must come after the other options)
+    #[clap(allow_hyphen_values = true, value_name = "TAGS")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Args)]
+struct SetPriority {
+    /// Tasks to change, in the same pattern as for marking them
+    #[clap(value_name = "TASK(S)", allow_hyphen_values = true)]
+    pub tasks: String,
+
+    /// New priority (high, medium or low) or "none" to remove it
+    pub priority: String,
+}
+
+#[derive(Args)]
+struct SetDue {
+    /// Tasks to change, in the same pattern as for marking them
+    #[clap(value_name = "TASK(S)", allow_hyphen_values = true)]
+    pub tasks: String,
+
+    /// New due date (in the same format as for adding tasks) or "none" to remove it
+    pub date: String,
+}
+
+#[derive(Args)]
+struct SetTags {
+    /// Tasks to change, in the same pattern as for marking them
+    #[clap(value_name = "TASK(S)", allow_hyphen_values = true)]
+    pub tasks: String,
+
+    /// Tags to add like +work and tags to remove like -personal
+    #[clap(allow_hyphen_values = true, required = true)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Args)]
+struct Edit {
+    /// Task to edit (like 3, 3.2 or #42)
+    #[clap(value_name = "TASK")]
+    pub task: Option<String>,
+
+    /// New text of the task, any +tag in it is added as a tag
+    #[clap(requires = "task")]
+    pub text: Option<String>,
+
+    /// Edit the text of the task in $EDITOR (the default when no text is given)
+    #[clap(long, short, action, value_parser, conflicts_with = "text")]
+    pub editor: bool,
+}
+
+#[derive(Args)]
+struct Search {
+    /// Text to find in the tasks, ignoring case
+    #[clap(value_name = "QUERY", allow_hyphen_values = true)]
+    pub query: String,
+
+    /// Find the tasks matching the query as a regex (starting it with (?i) ignores case)
+    #[clap(long, short = 'e', action, value_parser, conflicts_with = "fuzzy")]
+    pub regex: bool,
+
+    /// Find the tasks having the characters of the query in the same order,
+    /// like "dpl" for "deploy"
+    #[clap(long, short, action, value_parser)]
+    pub fuzzy: bool,
+}
+
+#[derive(Args)]
+#[clap(group(
+    ArgGroup::new("destination")
+        .required(true)
+        .args(&["to", "after", "top", "bottom"])
+    ))]
+struct Move {
+    /// Tasks to move, in the same pattern as for marking them
+    /// (they keep their order when moving multiple tasks)
+    #[clap(value_name = "TASK(S)", allow_hyphen_values = true)]
+    pub tasks: String,
+
+    /// Move the tasks so that the first one ends up at this position
+    #[clap(long, value_name = "POSITION")]
+    pub to: Option<u32>,
+
+    /// Move the tasks after the task at this position
+    #[clap(long, value_name = "POSITION")]
+    pub after: Option<u32>,
+
+    /// Move the tasks to the top of the list
+    #[clap(long, action, value_parser)]
+    pub top: bool,
+
+    /// Move the tasks to the bottom of the list
+    #[clap(long, action, value_parser)]
+    pub bottom: bool,
+}
+
+#[derive(Args)]
+struct Import {
+    /// File with the tasks to import
+    pub file: PathBuf,
+
+    /// Format of the file (legacy, todotxt, markdown, ics, csv or tsv), by default it is
+    /// chosen by the extension of the file like .txt or .md
+    ///
+    /// The columns of CSV and TSV files are found by the names in their first row
+    #[clap(long, short, value_name = "FORMAT", conflicts_with_all = ["legacy", "todotxt"])]
+    pub format: Option<Format>,
+
+    /// The file is in the text format saved by older versions of todo (todo_tasks.txt)
+    #[clap(long, action, value_parser, conflicts_with = "todotxt")]
+    pub legacy: bool,
+
+    /// The file is in the todo.txt format
+    #[clap(long, action, value_parser)]
+    pub todotxt: bool,
+
+    /// Only show the tasks which would be imported without adding them
+    #[clap(long, short = 'n', action, value_parser)]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+struct Export {
+    /// File to write the tasks to, they are written to the terminal without it
+    #[clap(conflicts_with = "sync")]
+    pub file: Option<PathBuf>,
+
+    /// Format to write the tasks in (todotxt, markdown, ics, csv or tsv), by default it is
+    /// chosen by the extension of the file like .txt or .md
+    ///
+    /// Subtasks are written after their parent in the todo.txt format
+    /// indented under their parent in Markdown and related to their parent in iCalendar
+    #[clap(long, short, value_name = "FORMAT", conflicts_with = "todotxt")]
+    pub format: Option<Format>,
+
+    /// Write the tasks in the todo.txt format
+    #[clap(long, action, value_parser)]
+    pub todotxt: bool,
+
+    /// Columns to write in CSV and TSV, like index,text,status,due
+    ///
+    /// The columns are index, id, text, status, priority, due, tags, contexts, extensions,
+    /// created, completed, created_at and completed_at
+    /// (by default index, id, text, status, priority, due and tags)
+    #[clap(long, short, value_name = "COLUMNS", value_delimiter = ',')]
+    pub columns: Vec<Column>,
+
+    /// Check or uncheck the items of an existing Markdown checklist to match the tasks
+    /// with the same text, without changing anything else in the file
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["format", "todotxt"])]
+    pub sync: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct Steps {
+    /// Number of changes to undo or redo
+    #[clap(default_value_t = 1)]
+    pub count: usize,
+}
+
+#[derive(Args)]
+struct Lists {
+    #[command(subcommand)]
+    command: Option<ListsCommand>,
+}
+
+#[derive(Subcommand)]
+enum ListsCommand {
+    /// Create a new list
+    New { name: String },
+
+    /// Rename a list
+    Rename { name: String, new_name: String },
+
+    /// Delete a list along with all of its tasks
+    Delete { name: String },
+
+    /// Move tasks from the current list to the end of another list
+    Move {
+        /// Tasks to move, in the same pattern as for marking them
+        #[clap(value_name = "TASK(S)", allow_hyphen_values = true)]
+        tasks: String,
+
+        /// List to move the tasks to
+        #[clap(value_name = "LIST")]
+        to: String,
+    },
+}
+
+// What a command did, which is printed as it happens for the text output and as a single
+// JSON object at the end for the JSON output
+#[derive(Default, Serialize)]
+struct Report {
+    #[serde(skip)]
+    output: OutputFormat,
+    // Set when the command printed its own JSON, like the listed tasks
+    #[serde(skip)]
+    printed: bool,
+    #[serde(skip)]
+    strict: bool,
+    // How the times of the tasks are shown in the text output, they aren't shown without it
+    #[serde(skip)]
+    times: Option<TimeFormat>,
+    // Views which can be used in selectors like @today
+    #[serde(skip)]
+    views: Views,
+    list: String,
+    messages: Vec<String>,
+    // The records of the added, changed or removed tasks
+    affected: Vec<Value>,
+    // Parts of selectors which were ignored since the tasks aren't in the list
+    ignored: Vec<String>,
+    warnings: Vec<String>,
+    // Tasks which were left unchanged since they have incomplete subtasks
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    blocked: Vec<u64>,
+}
+
+impl Report {
+    fn new(
+        output: OutputFormat,
+        strict: bool,
+        times: Option<TimeFormat>,
+        views: Views,
+        list: &str,
+    ) -> Self {
+        Report {
+            output,
+            strict,
+            times,
+            views,
+            list: list.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        self.output == OutputFormat::Text
+    }
+
+    fn message(&mut self, message: impl ToString) {
+        if self.is_text() {
+            println!("{}", message.to_string());
+        } else {
+            self.messages.push(message.to_string());
+        }
+    }
+
+    // Warnings are written to stderr for the text output
+    fn warning(&mut self, warning: impl ToString) {
+        if self.is_text() {
+            eprintln!("{}: {}", messages::SELECTOR_WARNING, warning.to_string());
+        } else {
+            self.warnings.push(warning.to_string());
+        }
+    }
+
+    // Parses the selector, reporting the parts which don't select tasks as they seem to
+    // (which fails instead when being strict)
+    fn select(&mut self, selector: &str, tasks: &[todo::Task]) -> Result<Selection, todo::Error> {
+        let today = clock::now().date_naive();
+        let selection = todo::parse_selector_with_views(selector, tasks, &self.views, today)?;
+        let issues = selection.issues();
+        if self.strict && !issues.is_empty() {
+            return Err(todo::Error::StrictSelector(issues.to_vec()));
+        }
+        for issue in issues {
+            if issue.is_ignored() {
+                self.ignored.push(issue.part().to_string());
+            }
+            self.warning(issue);
+        }
+        Ok(selection)
+    }
+
+    // Prints values (like the listed tasks) for the JSON output instead of the report
+    fn values(&mut self, values: Vec<Value>) {
+        todo::output::print_values(self.output, values);
+        self.printed = true;
+    }
+
+    // Asks the user to confirm, the prompt is written to stderr for the JSON output so that
+    // stdout only has JSON
+    fn confirm(&self, prompt: &str) -> bool {
+        if self.is_text() {
+            return confirm_prompt(prompt).unwrap_or(false);
+        }
+        eprint!("{prompt}");
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).is_ok() && input.trim().eq_ignore_ascii_case("y")
+    }
+
+    // Prints the report for the JSON output, errors are printed as JSON and still returned
+    // so that todo exits with a failure
+    fn finish(self, result: Result<(), todo::Error>) -> Result<(), todo::Error> {
+        if self.is_text() {
+            return result;
+        }
+        match result {
+            Ok(()) if self.printed => {}
+            Ok(()) => print_value(self.output, &json!(self)),
+            Err(e) => {
+                print_value(self.output, &json!({ "error": e.to_string() }));
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
 }
 
 // Deletes all tasks and handles errors
@@ -66,58 +586,637 @@ fn confirm_prompt(prompt: &str) -> io::Result<bool> {
     }
 }
 
-pub fn cli_run(tasks: &mut Vec<todo::Task>) -> Result<(), todo::Error> {
+// Parses a due date or "none" for removing it
+fn parse_due(date: &str) -> Result<Option<NaiveDate>, todo::Error> {
+    match date.to_lowercase().as_str() {
+        "none" => Ok(None),
+        date => todo::dates::parse_date(date, clock::now().date_naive()).map(Some),
+    }
+}
+
+// Parses a filter for the dates tasks were added or completed on, like "today" or ">=-7d"
+fn parse_date_filter(filter: &str) -> Result<DueFilter, todo::Error> {
+    DueFilter::parse_comparison(filter, clock::now().date_naive())
+}
+
+// Returns the IDs of the tasks of a list chosen by the selector, reporting which parts of the
+// selector were ignored (or failing for them when being strict)
+fn select_ids(
+    lists: &TaskLists,
+    list_name: &str,
+    selector: Option<&str>,
+    report: &mut Report,
+) -> Result<Vec<u64>, todo::Error> {
+    let Some(selector) = selector else {
+        return Ok(Vec::new());
+    };
+    let tasks = lists.get(list_name)?;
+    Ok(report.select(selector, tasks)?.ids(tasks))
+}
+
+// Changes the selected tasks of a list, reporting which tasks were changed
+// and which parts of the selector were ignored (or failing for them when being strict)
+fn change_tasks(
+    lists: &mut TaskLists,
+    list_name: &str,
+    selector: &str,
+    report: &mut Report,
+    f: impl FnMut(&mut Vec<todo::Task>, Vec<Range<u32>>),
+) -> Result<(), todo::Error> {
+    let ids = select_ids(lists, list_name, Some(selector), report)?;
+    change_selected(lists, list_name, &ids, report, f)
+}
+
+// Changes the tasks of a list with the IDs, wherever they are after earlier changes
+fn change_selected(
+    lists: &mut TaskLists,
+    list_name: &str,
+    ids: &[u64],
+    report: &mut Report,
+    f: impl FnMut(&mut Vec<todo::Task>, Vec<Range<u32>>),
+) -> Result<(), todo::Error> {
+    let tasks = lists.get_mut(list_name)?;
+    Selection::from_ids(ids, tasks).apply(tasks, f);
+    report
+        .affected
+        .extend(records_with_ids(list_name, tasks, ids));
+    Ok(())
+}
+
+// Shows the tasks of a list, or writes their records for the JSON output
+fn show_tasks(tasks: &[todo::Task], list_name: &str, report: &mut Report) {
+    if report.is_text() {
+        todo::display_tasks(tasks, report.times);
+    } else {
+        let listed: Vec<(usize, &todo::Task)> = tasks.iter().enumerate().collect();
+        report.values(task_records(list_name, &listed));
+    }
+}
+
+// Handles the subcommands of the lists command
+fn lists_run(
+    command: &Lists,
+    lists: &mut TaskLists,
+    current: &str,
+    report: &mut Report,
+) -> Result<(), todo::Error> {
+    match &command.command {
+        None if report.is_text() => todo::display_list_counts(lists),
+        None => {
+            let counts = lists.iter().map(|(name, tasks)| {
+                let done = tasks.iter().filter(|task| task.is_complete()).count();
+                json!({ "list": name, "open": tasks.len() - done, "done": done })
+            });
+            report.values(counts.collect());
+        }
+        Some(ListsCommand::New { name }) => {
+            lists.create(name)?;
+            report.message(messages::LIST_CREATED);
+        }
+        Some(ListsCommand::Rename { name, new_name }) => {
+            lists.rename(name, new_name)?;
+            report.message(messages::LIST_RENAMED);
+        }
+        Some(ListsCommand::Delete { name }) => {
+            let tasks = lists.get(name)?;
+            let listed: Vec<(usize, &todo::Task)> = tasks.iter().enumerate().collect();
+            let deleted = task_records(name, &listed);
+            if report.confirm(messages::LIST_DEL_ASK) {
+                lists.delete(name)?;
+                report.affected.extend(deleted);
+                report.message(messages::LIST_DELETED);
+            } else {
+                report.message(messages::DEL_CANCEL);
+            }
+        }
+        Some(ListsCommand::Move { tasks, to }) => {
+            let selection = report.select(tasks, lists.get(current)?)?;
+            let ids = selection.ids(lists.get(current)?);
+            lists.move_tasks(current, selection, to)?;
+            report
+                .affected
+                .extend(records_with_ids(to, lists.get(to)?, &ids));
+            report.message(messages::TASK_MOVED);
+        }
+    }
+    Ok(())
+}
+
+// Handles the edit command
+fn edit_run(
+    edit: &Edit,
+    lists: &mut TaskLists,
+    list_name: &str,
+    report: &mut Report,
+) -> Result<(), todo::Error> {
+    let tasks = lists.get_mut(list_name)?;
+
+    // Editing the whole list in the editor
+    let Some(selector) = &edit.task else {
+        let edited = todo::edit::open_editor(&todo::edit::to_editable(tasks))?;
+        let summary = todo::edit::apply_editable(tasks, &edited);
+        lists.assign_missing_ids();
+        report.message(format!(
+            "{} added, {} changed, {} removed",
+            summary.added, summary.changed, summary.removed
+        ));
+        return Ok(());
+    };
+
+    let path = todo::parse_task_path(selector, tasks)?;
+    let task = todo::task_at_mut(tasks, &path)
+        .ok_or_else(|| todo::Error::TaskNotFound(selector.clone()))?;
+    let id = task.id();
+
+    match &edit.text {
+        Some(text) => todo::edit::edit_task(task, text),
+        None => {
+            let edited = todo::edit::open_editor(&todo::edit::editable_text(task))?;
+            let edited = edited.lines().next().unwrap_or("").trim();
+            if edited.is_empty() {
+                report.message(messages::TASK_UNCHANGED);
+                return Ok(());
+            }
+            todo::edit::apply_editable_text(task, edited);
+        }
+    }
+    report
+        .affected
+        .extend(records_with_ids(list_name, lists.get(list_name)?, &[id]));
+    report.message(messages::TASK_EDITED);
+    Ok(())
+}
+
+// The format given for a file, or the one detected from its name (and data)
+fn file_format(
+    format: Option<Format>,
+    path: Option<&Path>,
+    data: Option<&str>,
+) -> Result<Format, todo::Error> {
+    format
+        .or_else(|| Format::detect(path?, data))
+        .ok_or_else(|| todo::Error::UnknownFormat(path.map(|path| path.display().to_string())))
+}
+
+fn import_run(
+    import: &Import,
+    lists: &mut TaskLists,
+    list_name: &str,
+    report: &mut Report,
+) -> Result<(), todo::Error> {
+    let import_error = |e: io::Error| todo::Error::ImportError(e.to_string());
+    let data = fs::read_to_string(&import.file).map_err(import_error)?;
+    let format = match (import.legacy, import.todotxt) {
+        (true, _) => Some(Format::Legacy),
+        (_, true) => Some(Format::TodoTxt),
+        _ => import.format,
+    };
+    let tasks = file_format(format, Some(&import.file), Some(&data))?.parse(&data)?;
+    let count = tasks.len();
+
+    // Importing into a copy of the lists shows the positions and IDs the tasks would get
+    let mut preview;
+    let imported_to = if import.dry_run {
+        preview = lists.clone();
+        &mut preview
+    } else {
+        lists
+    };
+    let start = imported_to.get(list_name)?.len();
+    imported_to.import_tasks(list_name, tasks)?;
+    let imported: Vec<(usize, &todo::Task)> = imported_to
+        .get(list_name)?
+        .iter()
+        .enumerate()
+        .skip(start)
+        .collect();
+
+    if !import.dry_run {
+        report.affected.extend(task_records(list_name, &imported));
+        report.message(format!("{} ({count})", messages::TASKS_IMPORTED));
+    } else if report.is_text() {
+        todo::display_listed_tasks(&imported, report.times);
+        report.message(format!("{} ({count})", messages::IMPORT_DRY_RUN));
+    } else {
+        report.affected.extend(task_records(list_name, &imported));
+        report.message(format!("{} ({count})", messages::IMPORT_DRY_RUN));
+    }
+    Ok(())
+}
+
+fn export_run(
+    export: &Export,
+    lists: &TaskLists,
+    list_name: &str,
+    report: &mut Report,
+) -> Result<(), todo::Error> {
+    let tasks = lists.get(list_name)?;
+    let export_error = |e: io::Error| todo::Error::ExportError(e.to_string());
+
+    // Only changing the checkboxes of an existing checklist
+    if let Some(path) = &export.sync {
+        let data = fs::read_to_string(path).map_err(export_error)?;
+        let (synced, changed) = todo::markdown::sync(&data, tasks);
+        if changed > 0 {
+            fs::write(path, synced).map_err(export_error)?;
+        }
+        report.message(format!("{} ({changed})", messages::CHECKLIST_SYNCED));
+        return Ok(());
+    }
+
+    let format = match export.todotxt {
+        true => Some(Format::TodoTxt),
+        false => export.format,
+    };
+    let columns = match export.columns.is_empty() {
+        true => &DEFAULT_COLUMNS[..],
+        false => &export.columns,
+    };
+    let data = file_format(format, export.file.as_deref(), None)?.format(tasks, columns)?;
+    match &export.file {
+        Some(path) => {
+            fs::write(path, data).map_err(export_error)?;
+            report.message(messages::TASKS_EXPORTED);
+        }
+        // The exported tasks are the output, even for the JSON output
+        None => {
+            print!("{data}");
+            report.printed = true;
+        }
+    }
+    Ok(())
+}
+
+// Asks whether to convert the tasks saved in the legacy text format
+pub fn confirm_legacy_migration() -> bool {
+    let migrate = confirm_prompt(messages::LEGACY_MIGRATE_ASK).unwrap_or(false);
+    if !migrate {
+        println!("{}", messages::LEGACY_NOT_MIGRATED);
+    }
+    migrate
+}
+
+// Undoes (or redoes) the last changes and shows which ones were undone
+fn undo_run(
+    steps: &Steps,
+    lists: &mut TaskLists,
+    journal: &mut Journal,
+    redo: bool,
+    report: &mut Report,
+) {
+    let mut changed = false;
+    for _ in 0..steps.count {
+        let entry = if redo {
+            journal.redo(lists)
+        } else {
+            journal.undo(lists)
+        };
+        let Some(entry) = entry else { break };
+        let done = if redo {
+            messages::REDONE
+        } else {
+            messages::UNDONE
+        };
+        report.message(format!("{done}: {}", entry.operation));
+        changed = true;
+    }
+    if !changed {
+        let nothing = if redo {
+            messages::NOTHING_TO_REDO
+        } else {
+            messages::NOTHING_TO_UNDO
+        };
+        report.message(nothing);
+    }
+}
+
+// The command line which was used to run todo, as shown in the history
+fn operation() -> String {
+    let args = env::args().skip(1).map(|arg| {
+        if arg.contains(char::is_whitespace) {
+            format!("\"{arg}\"")
+        } else {
+            arg
+        }
+    });
+    std::iter::once("todo".to_string())
+        .chain(args)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Runs the command, changes to the tasks are recorded in the journal so that they can be undone
+pub fn cli_run(
+    lists: &mut TaskLists,
+    journal: &mut Journal,
+    views: Views,
+) -> Result<(), todo::Error> {
     let cli = Cli::parse();
 
+    let undoing = matches!(
+        cli.commands,
+        Some(Commands::Undo(_) | Commands::Redo(_) | Commands::History)
+    );
+    let before = (!undoing).then(|| lists.clone());
+
+    let list_name = cli.list.as_deref().unwrap_or(DEFAULT_LIST);
+    let mut report = Report::new(cli.output, cli.strict, cli.verbose, views, list_name);
+    let result = run(cli, lists, journal, &mut report);
+    // Failed commands aren't recorded since their changes are never saved
+    if result.is_ok() {
+        if let Some(before) = before.filter(|before| before != lists) {
+            journal.record(&operation(), clock::now(), &before, lists);
+        }
+    }
+    report.finish(result)
+}
+
+fn run(
+    cli: Cli,
+    lists: &mut TaskLists,
+    journal: &mut Journal,
+    report: &mut Report,
+) -> Result<(), todo::Error> {
+    let list_name = cli.list.as_deref().unwrap_or(DEFAULT_LIST);
+
     // Checking for subcommands
     if let Some(command) = &cli.commands {
         match command {
             Commands::Add(new) => {
+                let due = match &new.due {
+                    Some(date) => parse_due(date)?,
+                    None => None,
+                };
+                let parent = match &new.parent {
+                    Some(parent) => todo::parse_task_path(parent, lists.get(list_name)?)?,
+                    None => Vec::new(),
+                };
+                let mut ids = Vec::new();
                 for item in &new.items {
-                    todo::add_task(tasks, item);
+                    let task = lists.add_subtask(list_name, &parent, item)?;
+                    task.set_priority(new.priority);
+                    task.set_due(due);
+                    ids.push(task.id());
+                }
+                report
+                    .affected
+                    .extend(records_with_ids(list_name, lists.get(list_name)?, &ids));
+                report.message(messages::TASK_ADDED);
+            }
+            Commands::List(list) => {
+                let status = match (list.open, list.done) {
+                    (true, _) => Some(TaskStatus::Incomplete),
+                    (_, true) => Some(TaskStatus::Complete),
+                    _ => None,
+                };
+                let options = ListOptions {
+                    by_priority: list.by_priority,
+                    due: list.due,
+                    created: list.created,
+                    completed: list.completed,
+                    tags: TagFilter::parse(&list.tags)?,
+                    status,
+                    sort: list.sort,
+                    reverse: list.reverse,
+                    limit: list.limit,
+                };
+                let today = clock::now().date_naive();
+                if report.is_text() {
+                    if list.all {
+                        todo::display_all_lists(lists, &options, today, report.times);
+                    } else {
+                        let listed = todo::list_tasks(lists.get(list_name)?, &options, today);
+                        todo::display_listed_tasks(&listed, report.times);
+                    }
+                } else {
+                    let mut records = Vec::new();
+                    for (name, tasks) in lists.iter() {
+                        if list.all || name == list_name {
+                            let listed = todo::list_tasks(tasks, &options, today);
+                            records.extend(task_records(name, &listed));
+                        }
+                    }
+                    lists.get(list_name)?;
+                    report.values(records);
+                }
+            }
+            Commands::Priority(change) => {
+                let priority = match change.priority.to_lowercase().as_str() {
+                    "none" => None,
+                    level => Some(level.parse()?),
+                };
+                change_tasks(lists, list_name, &change.tasks, report, |tasks, ranges| {
+                    todo::change_task_priority(tasks, ranges, priority)
+                })?;
+                report.message(messages::PRIORITY_CHANGED);
+            }
+            Commands::Due(change) => {
+                let due = parse_due(&change.date)?;
+                change_tasks(lists, list_name, &change.tasks, report, |tasks, ranges| {
+                    todo::change_task_due(tasks, ranges, due)
+                })?;
+                report.message(messages::DUE_CHANGED);
+            }
+            Commands::Tag(change) => {
+                let changes = TagFilter::parse(&change.tags)?;
+                change_tasks(lists, list_name, &change.tasks, report, |tasks, ranges| {
+                    todo::change_task_tags(tasks, ranges, &changes)
+                })?;
+                report.message(messages::TAGS_CHANGED);
+            }
+            Commands::View { name } => {
+                let today = clock::now().date_naive();
+                let options = report.views.options(name, today)?;
+                let listed = todo::list_tasks(lists.get(list_name)?, &options, today);
+                if report.is_text() {
+                    todo::display_listed_tasks(&listed, report.times);
+                } else {
+                    report.values(task_records(list_name, &listed));
+                }
+            }
+            Commands::Views if report.is_text() => todo::display_views(&report.views),
+            Commands::Views => {
+                let views = report
+                    .views
+                    .iter()
+                    .map(|(name, query)| json!({ "view": name, "query": query }));
+                let views = views.collect();
+                report.values(views);
+            }
+            Commands::Search(search) => {
+                let matcher = if search.regex {
+                    Matcher::regex(&search.query)?
+                } else if search.fuzzy {
+                    Matcher::fuzzy(&search.query)
+                } else {
+                    Matcher::substring(&search.query)
+                };
+                let matches = todo::search::search(lists.get(list_name)?, &matcher);
+                if report.is_text() {
+                    todo::display_matches(&matches, report.times);
+                } else {
+                    report.values(output::match_records(list_name, &matches));
+                }
+            }
+            Commands::Tags if report.is_text() => todo::display_tag_counts(lists.get(list_name)?),
+            Commands::Tags => {
+                let counts = todo::tags::tag_counts(lists.get(list_name)?);
+                let counts = counts
+                    .into_iter()
+                    .map(|(tag, (open, done))| json!({ "tag": tag, "open": open, "done": done }));
+                report.values(counts.collect());
+            }
+            Commands::Lists(command) => lists_run(command, lists, list_name, report)?,
+            Commands::Edit(edit) => edit_run(edit, lists, list_name, report)?,
+            Commands::Undo(steps) => undo_run(steps, lists, journal, false, report),
+            Commands::Redo(steps) => undo_run(steps, lists, journal, true, report),
+            Commands::History if report.is_text() => todo::display_history(journal),
+            Commands::History => {
+                let history = journal.history().map(|(entry, undone)| {
+                    json!({ "time": entry.time, "operation": entry.operation, "undone": undone })
+                });
+                report.values(history.collect());
+            }
+            Commands::Import(import) => import_run(import, lists, list_name, report)?,
+            Commands::Export(export) => export_run(export, lists, list_name, report)?,
+            Commands::Move(moving) => {
+                let destination = match (moving.to, moving.after, moving.top) {
+                    (Some(pos), _, _) => Destination::To(pos),
+                    (_, Some(pos), _) => Destination::After(pos),
+                    (_, _, true) => Destination::Top,
+                    _ => Destination::Bottom,
+                };
+                change_tasks(lists, list_name, &moving.tasks, report, |tasks, ranges| {
+                    todo::move_tasks(tasks, ranges, destination)
+                })?;
+                if report.is_text() {
+                    todo::display_tasks(lists.get(list_name)?, report.times);
                 }
-                println!("{}", messages::TASK_ADDED);
             }
         }
         return Ok(());
     }
 
-    // Removing specific tasks
-    if let Some(pattern) = cli.remove {
-        let ranges = todo::parse_pattern(&pattern)?;
-        todo::remove_tasks(tasks, ranges);
+    let changing = cli.remove.is_some()
+        || cli.mark.is_some()
+        || cli.unmark.is_some()
+        || cli.remove_marked
+        || cli.delete_all;
+
+    // Every selector is resolved against the tasks as they are before changing any of them,
+    // so a selector failing (like when being strict) leaves all the tasks unchanged
+    let removed = select_ids(lists, list_name, cli.remove.as_deref(), report)?;
+    let marked = select_ids(lists, list_name, cli.mark.as_deref(), report)?;
+    let unmarked = select_ids(lists, list_name, cli.unmark.as_deref(), report)?;
+
+    // Removing specific tasks, which are reported as they were before being removed
+    if cli.remove.is_some() {
+        let tasks = lists.get_mut(list_name)?;
+        report
+            .affected
+            .extend(records_with_ids(list_name, tasks, &removed));
+        Selection::from_ids(&removed, tasks).apply(tasks, todo::remove_tasks);
     }
 
     // Marking specific tasks as done
-    if let Some(pattern) = cli.mark {
-        let ranges = todo::parse_pattern(&pattern)?;
-        todo::change_task_status(tasks, ranges, TaskStatus::Complete);
+    if cli.mark.is_some() {
+        let mut blocked = Vec::new();
+        change_selected(lists, list_name, &marked, report, |tasks, ranges| {
+            blocked.extend(todo::complete_tasks(tasks, ranges, cli.cascade))
+        })?;
+        report
+            .affected
+            .retain(|record| !blocked.iter().any(|id| record["id"] == *id));
+        if !blocked.is_empty() {
+            let ids: Vec<String> = blocked.iter().map(|id| format!("#{id}")).collect();
+            report.message(format!(
+                "{}: {}",
+                messages::SUBTASKS_INCOMPLETE,
+                ids.join(", ")
+            ));
+            report.blocked = blocked;
+        }
     }
 
     // Unmarking specific tasks to set them incomplete
-    if let Some(pattern) = cli.unmark {
-        let ranges = todo::parse_pattern(&pattern)?;
-        todo::change_task_status(tasks, ranges, TaskStatus::Incomplete);
+    if cli.unmark.is_some() {
+        change_selected(lists, list_name, &unmarked, report, |tasks, ranges| {
+            todo::change_task_status(tasks, ranges, TaskStatus::Incomplete)
+        })?;
     }
 
+    let tasks = lists.get_mut(list_name)?;
+
     // Removing all marked Tasks
     if cli.remove_marked {
+        let listed: Vec<(usize, &todo::Task)> = tasks.iter().enumerate().collect();
+        let completed = task_records(list_name, &listed)
+            .into_iter()
+            .filter(|record| record["status"] == json!(TaskStatus::Complete));
+        report.affected.extend(completed);
         todo::remove_completed_tasks(tasks);
     }
 
     // Deleting all saved tasks
     if cli.delete_all {
-        match confirm_prompt(messages::DEL_ALL_ASK) {
-            Ok(true) => {
-                todo::remove_all(tasks);
-                println!("{}", messages::DEL_ALL)
-            }
-            _ => println!("{}", messages::DEL_CANCEL),
+        if report.confirm(messages::DEL_ALL_ASK) {
+            let listed: Vec<(usize, &todo::Task)> = tasks.iter().enumerate().collect();
+            report.affected.extend(task_records(list_name, &listed));
+            todo::remove_all(tasks);
+            report.message(messages::DEL_ALL)
+        } else {
+            report.message(messages::DEL_CANCEL)
         }
         return Ok(());
     }
 
-    todo::display_tasks(tasks);
+    // The changed tasks are reported instead of listing all tasks for the JSON output
+    if report.is_text() || !changing {
+        show_tasks(tasks, list_name, report);
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Three tasks where the second one is done
+    fn setup_lists() -> TaskLists {
+        let mut lists = TaskLists::default();
+        for text in ["Task 1", "Task 2", "Task 3"] {
+            lists.add_task(DEFAULT_LIST, text).unwrap();
+        }
+        lists.get_mut(DEFAULT_LIST).unwrap()[1].set_status(TaskStatus::Complete);
+        lists
+    }
+
+    // Runs todo with the arguments on the lists, writing the JSON output
+    fn run_args(args: &[&str], lists: &mut TaskLists) -> Result<(), todo::Error> {
+        let cli = Cli::try_parse_from(["todo", "--output", "json"].iter().chain(args)).unwrap();
+        let mut report = Report::new(cli.output, cli.strict, None, Views::default(), DEFAULT_LIST);
+        run(cli, lists, &mut Journal::default(), &mut report)
+    }
+
+    #[test]
+    fn selectors_are_resolved_before_changes() {
+        let mut lists = setup_lists();
+        run_args(&["--remove", "1", "--unmark", "2"], &mut lists).unwrap();
+        let tasks = lists.get(DEFAULT_LIST).unwrap();
+        let status: Vec<(&str, bool)> = tasks
+            .iter()
+            .map(|task| (task.text(), task.is_complete()))
+            .collect();
+        assert_eq!(status, vec![("Task 2", false), ("Task 3", false)]);
+    }
+
+    #[test]
+    fn strict_failure_leaves_tasks_unchanged() {
+        let mut lists = setup_lists();
+        let before = lists.clone();
+        let result = run_args(&["--strict", "--remove", "1", "--unmark", "9"], &mut lists);
+        assert!(matches!(result, Err(todo::Error::StrictSelector(_))));
+        assert_eq!(lists, before);
+    }
+}
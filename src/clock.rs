@@ -0,0 +1,73 @@
+// The clock which the times tasks are added and completed at come from, it can be replaced
+// (for the current thread) so that the times are known, and the tests of the crate start
+// with a fixed clock instead of the time of the system
+
+use chrono::{DateTime, Local};
+use std::cell::RefCell;
+
+pub trait Clock {
+    fn now(&self) -> DateTime<Local>;
+}
+
+// The time of the system
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+// Always the same time
+pub struct FixedClock(pub DateTime<Local>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Local> {
+        self.0
+    }
+}
+
+thread_local! {
+    static CLOCK: RefCell<Box<dyn Clock>> = RefCell::new(default_clock());
+}
+
+#[cfg(not(test))]
+fn default_clock() -> Box<dyn Clock> {
+    Box::new(SystemClock)
+}
+
+#[cfg(test)]
+fn default_clock() -> Box<dyn Clock> {
+    Box::new(FixedClock(tests::time()))
+}
+
+// Replaces the clock of the current thread
+pub fn set_clock(clock: impl Clock + 'static) {
+    CLOCK.with(|current| *current.borrow_mut() = Box::new(clock));
+}
+
+// The current time from the clock of the current thread
+pub fn now() -> DateTime<Local> {
+    CLOCK.with(|clock| clock.borrow().now())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // The time of the fixed clock, 2023-09-20 (a Wednesday like the dates of the other tests)
+    pub(crate) fn time() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2023, 9, 20, 9, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn replacing_the_clock() {
+        assert_eq!(now(), time());
+        let later = Local.with_ymd_and_hms(2023, 9, 21, 18, 0, 0).unwrap();
+        set_clock(FixedClock(later));
+        assert_eq!(now(), later);
+        set_clock(SystemClock);
+        assert!(now() > later);
+    }
+}
@@ -0,0 +1,551 @@
+// Reading and writing tasks as CSV (or TSV) for spreadsheets, with a row for every task
+// and a header row naming the columns
+
+use chrono::{DateTime, Local, NaiveDate};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Error, Task, TaskStatus};
+
+pub const CSV_SEPARATOR: char = ',';
+pub const TSV_SEPARATOR: char = '\t';
+const DATE_FORMAT: &str = "%Y-%m-%d";
+// Spreadsheets run fields starting with these as formulas (even after a tab or a carriage
+// return), so such fields are written after a quote (which spreadsheets don't show) and it is
+// removed again when importing
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+const FORMULA_ESCAPE: char = '\'';
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Column {
+    // The position of the task like 3 or 3.2 for subtasks, which also gives the subtasks
+    // their parent when importing
+    Index,
+    Id,
+    Text,
+    Status,
+    Priority,
+    Due,
+    Tags,
+    Contexts,
+    Extensions,
+    Created,
+    Completed,
+    // The times the task was added and completed at, like 2023-09-20T09:30:00+02:00
+    CreatedAt,
+    CompletedAt,
+}
+
+// The columns which are exported when no columns are chosen
+pub const DEFAULT_COLUMNS: [Column; 7] = [
+    Column::Index,
+    Column::Id,
+    Column::Text,
+    Column::Status,
+    Column::Priority,
+    Column::Due,
+    Column::Tags,
+];
+
+impl Column {
+    fn name(self) -> &'static str {
+        match self {
+            Column::Index => "index",
+            Column::Id => "id",
+            Column::Text => "text",
+            Column::Status => "status",
+            Column::Priority => "priority",
+            Column::Due => "due",
+            Column::Tags => "tags",
+            Column::Contexts => "contexts",
+            Column::Extensions => "extensions",
+            Column::Created => "created",
+            Column::Completed => "completed",
+            Column::CreatedAt => "created_at",
+            Column::CompletedAt => "completed_at",
+        }
+    }
+
+    fn value(self, task: &Task, index: &str) -> String {
+        let date = |date: Option<NaiveDate>| date.map(|date| date.to_string()).unwrap_or_default();
+        let time = |time: Option<DateTime<Local>>| time.map(|time| time.to_rfc3339());
+        let words = |words: Vec<&str>| words.join(" ");
+        match self {
+            Column::Index => index.to_string(),
+            Column::Id => task.id.to_string(),
+            Column::Text => task.text.clone(),
+            Column::Status => match task.status {
+                TaskStatus::Complete => "complete".to_string(),
+                TaskStatus::Incomplete => "incomplete".to_string(),
+            },
+            Column::Priority => task.priority.map(|p| p.to_string()).unwrap_or_default(),
+            Column::Due => date(task.due),
+            Column::Tags => words(task.tags.iter().map(String::as_str).collect()),
+            Column::Contexts => words(task.contexts.iter().map(String::as_str).collect()),
+            Column::Extensions => task
+                .extensions
+                .iter()
+                .map(|(key, value)| format!("{key}:{value}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Column::Created => date(task.created),
+            Column::Completed => date(task.completed),
+            Column::CreatedAt => time(task.created_at).unwrap_or_default(),
+            Column::CompletedAt => time(task.completed_at).unwrap_or_default(),
+        }
+    }
+
+    // Sets the field of the column from a value like the one from value(),
+    // the index and ID are not set since imported tasks get new ones
+    fn set(self, task: &mut Task, value: &str) -> Option<()> {
+        let date = |value: &str| match value {
+            "" => Some(None),
+            value => NaiveDate::parse_from_str(value, DATE_FORMAT).ok().map(Some),
+        };
+        let time = |value: &str| match value {
+            "" => Some(None),
+            value => DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|time| Some(time.with_timezone(&Local))),
+        };
+        // Tags and contexts may also be separated by commas and start with + or @
+        let words = |value: &str, prefix: char| {
+            value
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .map(|word| word.trim_start_matches(prefix))
+                .filter(|word| !word.is_empty())
+                .map(String::from)
+                .collect()
+        };
+        match self {
+            Column::Index | Column::Id => {}
+            Column::Text => task.text = value.to_string(),
+            Column::Status => task.status = parse_status(value)?,
+            Column::Priority => {
+                task.priority = match value {
+                    "" => None,
+                    value => Some(value.parse().ok()?),
+                }
+            }
+            Column::Due => task.due = date(value)?,
+            Column::Tags => task.tags = words(value, '+'),
+            Column::Contexts => task.contexts = words(value, '@'),
+            Column::Extensions => {
+                task.extensions = value
+                    .split_whitespace()
+                    .map(|word| {
+                        let (key, value) = word.split_once(':')?;
+                        Some((key.to_string(), value.to_string()))
+                    })
+                    .collect::<Option<_>>()?
+            }
+            Column::Created => task.created = date(value)?,
+            Column::Completed => task.completed = date(value)?,
+            // Tasks without the times may still have the dates from the other columns
+            Column::CreatedAt => {
+                if let Some(time) = time(value)? {
+                    task.set_created_at(Some(time));
+                }
+            }
+            Column::CompletedAt => {
+                if let Some(time) = time(value)? {
+                    task.set_completed_at(Some(time));
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+impl FromStr for Column {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "index" | "#" | "position" => Ok(Column::Index),
+            "id" => Ok(Column::Id),
+            "text" | "task" | "title" | "summary" => Ok(Column::Text),
+            "status" | "done" => Ok(Column::Status),
+            "priority" => Ok(Column::Priority),
+            "due" | "due date" => Ok(Column::Due),
+            "tags" | "projects" => Ok(Column::Tags),
+            "contexts" => Ok(Column::Contexts),
+            "extensions" => Ok(Column::Extensions),
+            "created" | "created date" => Ok(Column::Created),
+            "completed" | "completed date" => Ok(Column::Completed),
+            "created_at" | "created at" => Ok(Column::CreatedAt),
+            "completed_at" | "completed at" => Ok(Column::CompletedAt),
+            _ => Err(Error::ParseColumnError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+fn parse_status(status: &str) -> Option<TaskStatus> {
+    match status.to_lowercase().as_str() {
+        "complete" | "completed" | "done" | "x" | "yes" | "true" | "1" => {
+            Some(TaskStatus::Complete)
+        }
+        "incomplete" | "open" | "no" | "false" | "0" | "" => Some(TaskStatus::Incomplete),
+        _ => None,
+    }
+}
+
+// Fields with the separator, quotes or line breaks in them are quoted, with the quotes
+// in them written twice
+fn quote(field: &str, separator: char) -> String {
+    if field.contains([separator, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Fields which start with the escape before a formula are escaped too, so that it is only
+// removed from the fields which were escaped
+fn needs_escape(field: &str) -> bool {
+    match field.strip_prefix(FORMULA_ESCAPE) {
+        Some(rest) => needs_escape(rest),
+        None => field.starts_with(FORMULA_PREFIXES),
+    }
+}
+
+fn escape_formula(field: String) -> String {
+    if needs_escape(&field) {
+        format!("{FORMULA_ESCAPE}{field}")
+    } else {
+        field
+    }
+}
+
+fn unescape_formula(field: &str) -> &str {
+    match field.strip_prefix(FORMULA_ESCAPE) {
+        Some(rest) if needs_escape(rest) => rest,
+        _ => field,
+    }
+}
+
+fn write_row(out: &mut String, fields: impl IntoIterator<Item = String>, separator: char) {
+    let fields: Vec<String> = fields
+        .into_iter()
+        .map(|field| quote(&field, separator))
+        .collect();
+    out.push_str(&fields.join(&separator.to_string()));
+    out.push('\n');
+}
+
+// Writes the tasks (and their subtasks after them) with the columns in the given order
+pub fn format_tasks(tasks: &[Task], columns: &[Column], separator: char) -> String {
+    fn write_tasks(
+        tasks: &[Task],
+        prefix: &str,
+        columns: &[Column],
+        separator: char,
+        out: &mut String,
+    ) {
+        for (i, task) in tasks.iter().enumerate() {
+            let index = format!("{prefix}{}", i + 1);
+            let fields = columns
+                .iter()
+                .map(|column| escape_formula(column.value(task, &index)));
+            write_row(out, fields, separator);
+            write_tasks(
+                &task.subtasks,
+                &format!("{index}."),
+                columns,
+                separator,
+                out,
+            );
+        }
+    }
+
+    let mut out = String::new();
+    write_row(&mut out, columns.iter().map(Column::to_string), separator);
+    write_tasks(tasks, "", columns, separator, &mut out);
+    out
+}
+
+// Splits the data into rows of fields along with the number of the line they start on,
+// quoted fields may have separators, quotes (written twice) and line breaks in them
+fn parse_rows(data: &str, separator: char) -> Vec<(usize, Vec<String>)> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = line;
+    let mut chars = data.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                '"' => quoted = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                c => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            c if c == separator => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push((start, std::mem::take(&mut row)));
+                line += 1;
+                start = line;
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push((start, row));
+    }
+    rows
+}
+
+// Parses the rows into tasks by the names of the columns in the header row, columns with
+// other names are skipped. Tasks with an index like 3.2 are added as subtasks of the task
+// with the index 3. The tasks don't have IDs yet
+pub fn parse_tasks(data: &str, separator: char) -> Result<Vec<Task>, Error> {
+    // Spreadsheets may start the file with a byte order mark
+    let data = data.trim_start_matches('\u{feff}');
+    let mut rows = parse_rows(data, separator)
+        .into_iter()
+        .filter(|(_, row)| row.iter().any(|field| !field.trim().is_empty()));
+    let Some((_, header)) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let columns: Vec<Option<Column>> = header.iter().map(|name| name.parse().ok()).collect();
+    if !columns.contains(&Some(Column::Text)) {
+        return Err(Error::MissingTextColumn);
+    }
+
+    let mut tasks: Vec<Task> = Vec::new();
+    // The paths of the tasks by their index, for adding subtasks to them
+    let mut paths: HashMap<String, Vec<usize>> = HashMap::new();
+    for (line, row) in rows {
+        let mut task = Task::default();
+        let mut index = None;
+        for (column, value) in columns.iter().zip(&row) {
+            let (Some(column), value) = (column, unescape_formula(value.trim())) else {
+                continue;
+            };
+            if *column == Column::Index {
+                index = Some(value);
+            }
+            column
+                .set(&mut task, value)
+                .ok_or_else(|| Error::ParseCsvError(line, column.to_string()))?;
+        }
+        if task.text.is_empty() {
+            return Err(Error::ParseCsvError(line, Column::Text.to_string()));
+        }
+
+        // Tasks whose parent isn't in the file are added as top level tasks
+        let mut path = index
+            .and_then(|index| index.rsplit_once('.'))
+            .and_then(|(parent, _)| paths.get(parent))
+            .cloned()
+            .unwrap_or_default();
+        // The paths are of tasks which were already added
+        let siblings = crate::subtasks_mut(&mut tasks, &path).unwrap();
+        siblings.push(task);
+        if let Some(index) = index {
+            path.push(siblings.len() - 1);
+            paths.insert(index.to_string(), path);
+        }
+    }
+    Ok(tasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    const ALL_COLUMNS: [Column; 13] = [
+        Column::Index,
+        Column::Id,
+        Column::Text,
+        Column::Status,
+        Column::Priority,
+        Column::Due,
+        Column::Tags,
+        Column::Contexts,
+        Column::Extensions,
+        Column::Created,
+        Column::Completed,
+        Column::CreatedAt,
+        Column::CompletedAt,
+    ];
+
+    fn setup_tasks() -> Vec<Task> {
+        let mut tasks = Vec::new();
+        let task = crate::add_task(&mut tasks, "Say \"hi\", then leave\nquietly");
+        task.add_tag("work");
+        task.add_tag("home");
+        task.set_priority(Some(Priority::Medium));
+        task.set_due(NaiveDate::from_ymd_opt(2026, 10, 20));
+        task.contexts.insert("office".to_string());
+        task.extensions.push(("rec".to_string(), "1w".to_string()));
+        crate::add_subtask(&mut tasks, &[0], "Task\t1.1").unwrap();
+        crate::add_subtask(&mut tasks, &[0, 0], "Task 1.1.1").unwrap();
+        crate::add_task(&mut tasks, "Task 2").set_status(TaskStatus::Complete);
+        tasks
+    }
+
+    #[test]
+    fn formatting_csv() {
+        let columns = [Column::Index, Column::Text, Column::Status, Column::Tags];
+        assert_eq!(
+            format_tasks(&setup_tasks(), &columns, CSV_SEPARATOR),
+            "index,text,status,tags\n\
+            1,\"Say \"\"hi\"\", then leave\nquietly\",incomplete,home work\n\
+            1.1,Task\t1.1,incomplete,\n\
+            1.1.1,Task 1.1.1,incomplete,\n\
+            2,Task 2,complete,\n"
+        );
+        assert!(format_tasks(&setup_tasks(), &columns, TSV_SEPARATOR)
+            .contains("1.1\t\"Task\t1.1\"\tincomplete\t\n"));
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let tasks = setup_tasks();
+        for separator in [CSV_SEPARATOR, TSV_SEPARATOR] {
+            let data = format_tasks(&tasks, &ALL_COLUMNS, separator);
+            let mut parsed = parse_tasks(&data, separator).unwrap();
+            crate::assign_missing_ids(&mut parsed);
+            assert_eq!(parsed, tasks);
+        }
+    }
+
+    #[test]
+    fn parsing_csv_by_header() {
+        let data = "\u{feff}Notes,Title,Done,Due Date,#\r\n\
+            x,Task 1,yes,2026-10-20,1\r\n\
+            ,,,,\r\n\
+            ,Task 2,,,5.1\r\n";
+        let tasks = parse_tasks(data, CSV_SEPARATOR).unwrap();
+        let parsed: Vec<(&str, bool)> = tasks
+            .iter()
+            .map(|task| (task.text(), task.is_complete()))
+            .collect();
+        assert_eq!(parsed, vec![("Task 1", true), ("Task 2", false)]);
+        assert_eq!(tasks[0].due(), NaiveDate::from_ymd_opt(2026, 10, 20));
+    }
+
+    #[test]
+    fn invalid_csv() {
+        assert_eq!(
+            parse_tasks("id,status\n1,complete\n", CSV_SEPARATOR),
+            Err(Error::MissingTextColumn)
+        );
+        assert_eq!(
+            parse_tasks("text,due\nTask 1,\nTask 2,soon\n", CSV_SEPARATOR),
+            Err(Error::ParseCsvError(3, "due".to_string()))
+        );
+        assert_eq!(parse_tasks("", CSV_SEPARATOR), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        // The rows before the invalid one span three lines
+        let data = "text,due\n\"Task 1\nwith notes\",\n\nTask 2,soon\n";
+        assert_eq!(
+            parse_tasks(data, CSV_SEPARATOR),
+            Err(Error::ParseCsvError(5, "due".to_string()))
+        );
+    }
+
+    #[test]
+    fn escaping_formulas() {
+        let mut tasks = Vec::new();
+        for text in [
+            "=SUM(A1:A2)",
+            "+1 555",
+            "-fix",
+            "@home",
+            "'quoted",
+            "'=x",
+            "a=b",
+        ] {
+            crate::add_task(&mut tasks, text);
+        }
+        let data = format_tasks(&tasks, &[Column::Text], CSV_SEPARATOR);
+        assert_eq!(
+            data,
+            "text\n'=SUM(A1:A2)\n'+1 555\n'-fix\n'@home\n'quoted\n''=x\na=b\n"
+        );
+        let texts: Vec<String> = parse_tasks(&data, CSV_SEPARATOR)
+            .unwrap()
+            .into_iter()
+            .map(|task| task.text)
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                "=SUM(A1:A2)",
+                "+1 555",
+                "-fix",
+                "@home",
+                "'quoted",
+                "'=x",
+                "a=b"
+            ]
+        );
+    }
+
+    #[test]
+    fn escaping_tabs_and_returns() {
+        let mut tasks = Vec::new();
+        for text in ["\t=1+2", "\rReturn", "'\tquoted"] {
+            crate::add_task(&mut tasks, "Task").text = text.to_string();
+        }
+        for separator in [CSV_SEPARATOR, TSV_SEPARATOR] {
+            let data = format_tasks(&tasks, &[Column::Text], separator);
+            // Fields with tabs in them are quoted in TSV files
+            let first = match separator {
+                CSV_SEPARATOR => "'\t=1+2",
+                _ => "\"'\t=1+2\"",
+            };
+            assert_eq!(data.lines().nth(1), Some(first));
+            assert!(data.contains("\"'\rReturn\""));
+            assert!(data.contains("''\tquoted"));
+            let texts: Vec<String> = parse_tasks(&data, separator)
+                .unwrap()
+                .into_iter()
+                .map(|task| task.text)
+                .collect();
+            assert_eq!(texts, vec!["\t=1+2", "\rReturn", "'\tquoted"]);
+        }
+    }
+
+    #[test]
+    fn importing_dates_without_times() {
+        // Like a task from a todo.txt file, which only has the dates
+        let mut tasks = Vec::new();
+        let task = crate::add_task(&mut tasks, "Task 1");
+        task.created_at = None;
+        task.created = NaiveDate::from_ymd_opt(2026, 10, 1);
+        task.status = TaskStatus::Complete;
+        task.completed = NaiveDate::from_ymd_opt(2026, 10, 2);
+
+        let data = format_tasks(&tasks, &ALL_COLUMNS, CSV_SEPARATOR);
+        let mut parsed = parse_tasks(&data, CSV_SEPARATOR).unwrap();
+        crate::assign_missing_ids(&mut parsed);
+        assert_eq!(parsed, tasks);
+    }
+}
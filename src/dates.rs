@@ -0,0 +1,296 @@
+// Parsing of user entered dates, filtering of tasks by their dates and displaying the
+// times tasks were added and completed at
+
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, Weekday};
+use std::str::FromStr;
+
+use crate::{Error, Task};
+
+// Parses a date like "2023-10-20", "today", "tomorrow", "fri", "+3d", "+2w", "+1m",
+// "next week" or "next month" relative to the given date, offsets like "-3d" are in the past
+// (weekdays always refer to the upcoming day, so "wed" on a Wednesday is a week later)
+pub fn parse_date(input: &str, today: NaiveDate) -> Result<NaiveDate, Error> {
+    let input = input.trim().to_lowercase();
+
+    if let Ok(date) = NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+        return Ok(date);
+    }
+
+    let date = match input.as_str() {
+        "today" => Some(today),
+        "tomorrow" | "tom" => today.checked_add_days(Days::new(1)),
+        "yesterday" => today.checked_sub_days(Days::new(1)),
+        "next week" => today.checked_add_days(Days::new(7)),
+        "next month" => today.checked_add_months(Months::new(1)),
+        relative if relative.starts_with(['+', '-']) => parse_offset(relative, today),
+        day => match day.parse::<Weekday>() {
+            Ok(weekday) => Some(next_weekday(weekday, today)),
+            Err(_) => None,
+        },
+    };
+    date.ok_or(Error::ParseDateError)
+}
+
+// Parses an offset like "+3d", "+2w" or "+1m" and adds it to the given date
+// (or subtracts it for an offset like "-3d")
+fn parse_offset(offset: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let (sign, offset) = offset.split_at(1);
+    let unit = offset.chars().last()?;
+    let count: u32 = offset[..offset.len() - unit.len_utf8()].parse().ok()?;
+
+    match (unit, sign) {
+        ('d', "+") => today.checked_add_days(Days::new(count.into())),
+        ('d', _) => today.checked_sub_days(Days::new(count.into())),
+        ('w', "+") => today.checked_add_days(Days::new(u64::from(count) * 7)),
+        ('w', _) => today.checked_sub_days(Days::new(u64::from(count) * 7)),
+        ('m', "+") => today.checked_add_months(Months::new(count)),
+        ('m', _) => today.checked_sub_months(Months::new(count)),
+        _ => None,
+    }
+}
+
+// Returns the next date (after today) which falls on the given weekday
+fn next_weekday(weekday: Weekday, today: NaiveDate) -> NaiveDate {
+    let days_ahead =
+        (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+    let days_ahead = if days_ahead == 0 { 7 } else { days_ahead };
+    today + Days::new(days_ahead.into())
+}
+
+// Filters for the due date of a task
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DueFilter {
+    // Incomplete tasks due before today
+    Overdue,
+    // Tasks due today
+    Today,
+    // Tasks due anytime in the current week (Monday to Sunday)
+    ThisWeek,
+    // Tasks due before, on or after a date, like "<=today"
+    Before(NaiveDate),
+    OnOrBefore(NaiveDate),
+    On(NaiveDate),
+    OnOrAfter(NaiveDate),
+    After(NaiveDate),
+}
+
+impl DueFilter {
+    pub fn matches(self, task: &Task, today: NaiveDate) -> bool {
+        match task.due() {
+            Some(_) if self == DueFilter::Overdue && task.is_complete() => false,
+            Some(due) => self.matches_date(due, today),
+            None => false,
+        }
+    }
+
+    // Whether any date matches, which is how tasks are filtered by the dates they were
+    // added and completed on (where "overdue" is any date before today)
+    pub fn matches_date(self, date: NaiveDate, today: NaiveDate) -> bool {
+        match self {
+            DueFilter::Overdue => date < today,
+            DueFilter::Today => date == today,
+            DueFilter::ThisWeek => date.iso_week() == today.iso_week(),
+            DueFilter::Before(other) => date < other,
+            DueFilter::OnOrBefore(other) => date <= other,
+            DueFilter::On(other) => date == other,
+            DueFilter::OnOrAfter(other) => date >= other,
+            DueFilter::After(other) => date > other,
+        }
+    }
+
+    // Parses a filter like "overdue", "today" or "week", or a comparison with a date
+    // like "<=today", ">2023-10-20" or "=fri" (dates are parsed like parse_date(), and
+    // a date without a comparison like "yesterday" is the same as "=yesterday")
+    pub fn parse_comparison(input: &str, today: NaiveDate) -> Result<Self, Error> {
+        if let Ok(filter) = input.parse() {
+            return Ok(filter);
+        }
+        let operator_len = input.find(|c| !matches!(c, '<' | '>' | '=')).unwrap_or(0);
+        let (operator, date) = input.split_at(operator_len);
+        let date = parse_date(date, today).map_err(|_| Error::ParseDueFilterError)?;
+        match operator {
+            "<" => Ok(DueFilter::Before(date)),
+            "<=" => Ok(DueFilter::OnOrBefore(date)),
+            "" | "=" => Ok(DueFilter::On(date)),
+            ">=" => Ok(DueFilter::OnOrAfter(date)),
+            ">" => Ok(DueFilter::After(date)),
+            _ => Err(Error::ParseDueFilterError),
+        }
+    }
+}
+
+impl FromStr for DueFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "overdue" => Ok(DueFilter::Overdue),
+            "today" => Ok(DueFilter::Today),
+            "week" | "this week" | "this-week" => Ok(DueFilter::ThisWeek),
+            _ => Err(Error::ParseDueFilterError),
+        }
+    }
+}
+
+// How the times tasks were added and completed at are displayed
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TimeFormat {
+    // Like "3d ago"
+    Relative,
+    // Like "2023-09-20 09:30"
+    Absolute,
+}
+
+impl TimeFormat {
+    pub fn format(self, time: DateTime<Local>, now: DateTime<Local>) -> String {
+        match self {
+            TimeFormat::Absolute => time.format("%Y-%m-%d %H:%M").to_string(),
+            TimeFormat::Relative => {
+                let minutes = (now - time).num_minutes();
+                match minutes {
+                    ..=0 => "just now".to_string(),
+                    1..=59 => format!("{minutes}m ago"),
+                    60..=1439 => format!("{}h ago", minutes / 60),
+                    _ => format!("{}d ago", minutes / 1440),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for TimeFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "relative" => Ok(TimeFormat::Relative),
+            "absolute" => Ok(TimeFormat::Absolute),
+            _ => Err(Error::ParseTimeFormatError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // A Wednesday
+    fn now() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 9, 20).unwrap()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn parsing_due_comparisons() {
+        let parse = |input| DueFilter::parse_comparison(input, now());
+        assert_eq!(parse("<=today"), Ok(DueFilter::OnOrBefore(now())));
+        assert_eq!(
+            parse(">2023-10-01"),
+            Ok(DueFilter::After(date(2023, 10, 1)))
+        );
+        assert_eq!(parse("=fri"), Ok(DueFilter::On(date(2023, 9, 22))));
+        assert_eq!(parse("overdue"), Ok(DueFilter::Overdue));
+        assert_eq!(parse("yesterday"), Ok(DueFilter::On(date(2023, 9, 19))));
+        assert_eq!(parse("<someday"), Err(Error::ParseDueFilterError));
+        assert_eq!(parse("today+1"), Err(Error::ParseDueFilterError));
+    }
+
+    #[test]
+    fn parsing_iso_date() {
+        assert_eq!(parse_date("2023-10-01", now()), Ok(date(2023, 10, 1)));
+        assert_eq!(parse_date("2023-13-01", now()), Err(Error::ParseDateError));
+    }
+
+    #[test]
+    fn parsing_named_days() {
+        assert_eq!(parse_date("today", now()), Ok(date(2023, 9, 20)));
+        assert_eq!(parse_date("Tomorrow", now()), Ok(date(2023, 9, 21)));
+        assert_eq!(parse_date("next week", now()), Ok(date(2023, 9, 27)));
+        assert_eq!(parse_date("next month", now()), Ok(date(2023, 10, 20)));
+    }
+
+    #[test]
+    fn parsing_weekdays() {
+        assert_eq!(parse_date("fri", now()), Ok(date(2023, 9, 22)));
+        assert_eq!(parse_date("monday", now()), Ok(date(2023, 9, 25)));
+        assert_eq!(parse_date("wed", now()), Ok(date(2023, 9, 27)));
+    }
+
+    #[test]
+    fn parsing_offsets() {
+        assert_eq!(parse_date("+3d", now()), Ok(date(2023, 9, 23)));
+        assert_eq!(parse_date("+2w", now()), Ok(date(2023, 10, 4)));
+        assert_eq!(parse_date("+1m", now()), Ok(date(2023, 10, 20)));
+        assert_eq!(parse_date("-3d", now()), Ok(date(2023, 9, 17)));
+        assert_eq!(parse_date("-1w", now()), Ok(date(2023, 9, 13)));
+        assert_eq!(parse_date("+3", now()), Err(Error::ParseDateError));
+        assert_eq!(parse_date("+xd", now()), Err(Error::ParseDateError));
+    }
+
+    #[test]
+    fn invalid_dates() {
+        assert_eq!(parse_date("someday", now()), Err(Error::ParseDateError));
+        assert_eq!(parse_date("", now()), Err(Error::ParseDateError));
+    }
+
+    #[test]
+    fn filtering_by_due_date() {
+        let mut overdue = Task::new("Overdue");
+        overdue.set_due(Some(date(2023, 9, 18)));
+        let mut today = Task::new("Today");
+        today.set_due(Some(date(2023, 9, 20)));
+        let mut sunday = Task::new("Sunday");
+        sunday.set_due(Some(date(2023, 9, 24)));
+        let mut next_monday = Task::new("Next Monday");
+        next_monday.set_due(Some(date(2023, 9, 25)));
+        let no_due = Task::new("No due date");
+
+        let tasks = [overdue, today, sunday, next_monday, no_due];
+        let matching = |filter: DueFilter| -> Vec<&str> {
+            tasks
+                .iter()
+                .filter(|task| filter.matches(task, now()))
+                .map(|task| task.text())
+                .collect()
+        };
+
+        assert_eq!(matching(DueFilter::Overdue), vec!["Overdue"]);
+        assert_eq!(matching(DueFilter::Today), vec!["Today"]);
+        assert_eq!(
+            matching(DueFilter::ThisWeek),
+            vec!["Overdue", "Today", "Sunday"]
+        );
+    }
+
+    #[test]
+    fn completed_tasks_are_not_overdue() {
+        let mut task = Task::new("Done");
+        task.set_due(Some(date(2023, 9, 1)));
+        task.set_status(crate::TaskStatus::Complete);
+        assert!(!DueFilter::Overdue.matches(&task, now()));
+    }
+
+    #[test]
+    fn formatting_times() {
+        let time = |d, h, m| Local.with_ymd_and_hms(2023, 9, d, h, m, 0).unwrap();
+        let now = time(20, 9, 30);
+        let relative = |then| TimeFormat::Relative.format(then, now);
+        assert_eq!(relative(time(20, 9, 30)), "just now");
+        assert_eq!(relative(time(20, 9, 5)), "25m ago");
+        assert_eq!(relative(time(19, 23, 0)), "10h ago");
+        assert_eq!(relative(time(17, 8, 0)), "3d ago");
+        assert_eq!(
+            TimeFormat::Absolute.format(time(17, 8, 0), now),
+            "2023-09-17 08:00"
+        );
+        assert_eq!("Absolute".parse(), Ok(TimeFormat::Absolute));
+        assert_eq!(
+            "ago".parse::<TimeFormat>(),
+            Err(Error::ParseTimeFormatError)
+        );
+    }
+}
@@ -0,0 +1,400 @@
+// Editing the text of tasks, either directly or through the user's text editor
+
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::hash::BuildHasher;
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::{env, fs, process};
+
+use crate::{tags, Error, Task, TaskStatus};
+
+// Spaces used for every level of subtasks in the editable text
+const INDENT: &str = "    ";
+
+// Names which are tried for the file opened in the editor before giving up
+const TEMP_FILE_ATTEMPTS: u32 = 16;
+
+const EDITABLE_HEADER: &str = "\
+# Edit, add, remove, reorder or (un)check tasks, indent lines to make subtasks.
+# Each task ends with its ID, new tasks don't need one. Lines starting with # are ignored.
+";
+
+// Changes the text of a task, the +tags in the new text replace the tags of the task
+pub fn edit_task(task: &mut Task, text: &str) {
+    let (text, tags) = tags::extract_tags(text.trim());
+    task.text = text;
+    task.tags = tags;
+}
+
+// The text of a task along with its tags (which aren't already within the text),
+// as shown in the editor
+pub fn editable_text(task: &Task) -> String {
+    let tags = task.tags.iter().map(|tag| format!("+{tag}"));
+    let tags = tags.filter(|tag| !task.has_word(tag));
+    task.text.clone() + &tags.map(|tag| format!(" {tag}")).collect::<String>()
+}
+
+// Changes the text and tags of a task to the ones in a text from editable_text(),
+// an unchanged text is kept as it is (along with the tags within it)
+pub fn apply_editable_text(task: &mut Task, text: &str) {
+    if text.trim() != editable_text(task) {
+        edit_task(task, text);
+    }
+}
+
+// Writes the tasks (and their subtasks) as lines like "[x] Text +tag #12"
+pub fn to_editable(tasks: &[Task]) -> String {
+    fn write_tasks(tasks: &[Task], depth: usize, out: &mut String) {
+        for task in tasks {
+            let check = if task.is_complete() { "x" } else { " " };
+            out.push_str(&format!(
+                "{}[{check}] {} #{}\n",
+                INDENT.repeat(depth),
+                editable_text(task),
+                task.id
+            ));
+            write_tasks(&task.subtasks, depth + 1, out);
+        }
+    }
+
+    let mut out = EDITABLE_HEADER.to_string();
+    write_tasks(tasks, 0, &mut out);
+    out
+}
+
+// Number of tasks changed by apply_editable()
+#[derive(Debug, Default, PartialEq)]
+pub struct EditSummary {
+    pub added: usize,
+    pub changed: usize,
+    pub removed: usize,
+}
+
+// A single line of the edited text
+struct EditedLine<'a> {
+    depth: usize,
+    status: TaskStatus,
+    // The text with the ID at the end removed
+    text: &'a str,
+    id: Option<u64>,
+    full_text: &'a str,
+}
+
+fn parse_line(line: &str) -> Option<EditedLine<'_>> {
+    let content = line.trim_start();
+    if content.is_empty() || content.starts_with('#') {
+        return None;
+    }
+
+    let indent = &line[..line.len() - content.len()];
+    let depth = indent.replace('\t', INDENT).len() / INDENT.len();
+
+    let (status, text) = if let Some(text) = content.strip_prefix("[ ]") {
+        (TaskStatus::Incomplete, text)
+    } else if let Some(text) = content
+        .strip_prefix("[x]")
+        .or_else(|| content.strip_prefix("[X]"))
+    {
+        (TaskStatus::Complete, text)
+    } else {
+        (TaskStatus::Incomplete, content)
+    };
+
+    let full_text = text.trim();
+    let (text, id) = match full_text.rsplit_once(' ') {
+        Some((rest, id)) if id.starts_with('#') => match id[1..].parse() {
+            Ok(id) => (rest.trim_end(), Some(id)),
+            Err(_) => (full_text, None),
+        },
+        _ => (full_text, None),
+    };
+
+    Some(EditedLine {
+        depth,
+        status,
+        text,
+        id,
+        full_text,
+    })
+}
+
+// Replaces the tasks with the ones in the edited text from to_editable(), tasks keep
+// everything else (like their priority) as long as their ID is still in the text,
+// tasks whose ID is missing are removed and lines without an ID are added as new tasks
+// (which still need an ID to be assigned to them)
+pub fn apply_editable(tasks: &mut Vec<Task>, edited: &str) -> EditSummary {
+    fn take_all(tasks: Vec<Task>, out: &mut HashMap<u64, Task>) {
+        for mut task in tasks {
+            take_all(std::mem::take(&mut task.subtasks), out);
+            out.insert(task.id, task);
+        }
+    }
+
+    let mut existing = HashMap::new();
+    take_all(std::mem::take(tasks), &mut existing);
+
+    let mut summary = EditSummary::default();
+    // The IDs of the tasks on the lines so far, lines copied with them become new tasks
+    let mut taken = HashSet::new();
+    // Tasks which can still get subtasks, from the top level task down to the last added one
+    let mut parents: Vec<Task> = Vec::new();
+
+    for line in edited.lines().filter_map(parse_line) {
+        // Text ending with something like "#99" which isn't the ID of a task is kept as it is
+        let (mut task, text) = match line.id.and_then(|id| existing.remove(&id)) {
+            Some(task) => {
+                taken.insert(task.id);
+                (task, line.text)
+            }
+            None => {
+                summary.added += 1;
+                let mut task = Task::new("");
+                task.set_created_at(Some(crate::clock::now()));
+                match line.id.is_some_and(|id| taken.contains(&id)) {
+                    true => (task, line.text),
+                    false => (task, line.full_text),
+                }
+            }
+        };
+
+        let old = (task.text.clone(), task.status, task.tags.clone());
+        apply_editable_text(&mut task, text);
+        task.set_status(line.status);
+        if task.id != 0 && old != (task.text.clone(), task.status, task.tags.clone()) {
+            summary.changed += 1;
+        }
+
+        // A line can be at most one level deeper than the line before it
+        while parents.len() > line.depth {
+            close_parent(&mut parents, tasks);
+        }
+        parents.push(task);
+    }
+    while !parents.is_empty() {
+        close_parent(&mut parents, tasks);
+    }
+
+    summary.removed = existing.len();
+    summary
+}
+
+// Adds the last parent to the subtasks of the one before it (or to the top level tasks)
+fn close_parent(parents: &mut Vec<Task>, tasks: &mut Vec<Task>) {
+    if let Some(task) = parents.pop() {
+        match parents.last_mut() {
+            Some(parent) => parent.subtasks.push(task),
+            None => tasks.push(task),
+        }
+    }
+}
+
+// A file in the temporary directory which is removed when it is dropped
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    // Creates a new file with a random name, a file which is already there (like a link to
+    // another file) is never opened and another name is tried instead
+    fn create(text: &str) -> io::Result<Self> {
+        for _ in 0..TEMP_FILE_ATTEMPTS {
+            let random = RandomState::new().hash_one(process::id());
+            let name = format!("todo-edit-{}-{random:016x}.txt", process::id());
+            let path = env::temp_dir().join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let temp = TempFile { path };
+                    file.write_all(text.as_bytes())?;
+                    return Ok(temp);
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(io::Error::new(
+            ErrorKind::AlreadyExists,
+            "no free name for a temporary file",
+        ))
+    }
+
+    // Editors may replace the file instead of writing to it, so it is opened again
+    fn read(&self) -> io::Result<String> {
+        let mut text = String::new();
+        File::open(&self.path)?.read_to_string(&mut text)?;
+        Ok(text)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Opens the text in the editor set in $EDITOR (or vi) and returns the edited text
+pub fn open_editor(text: &str) -> Result<String, Error> {
+    let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let editor_error = |e: io::Error| Error::EditorError(e.to_string());
+
+    // The file is removed whenever this returns
+    let file = TempFile::create(text).map_err(editor_error)?;
+
+    // The editor may also contain arguments like "code --wait"
+    let mut args = editor.split_whitespace();
+    let program = args.next().unwrap_or("vi");
+    let status = process::Command::new(program)
+        .args(args)
+        .arg(&file.path)
+        .status()
+        .map_err(editor_error)?;
+
+    match status {
+        s if s.success() => file.read().map_err(editor_error),
+        s => Err(Error::EditorError(s.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_task;
+    use std::collections::BTreeSet;
+
+    fn setup_tasks() -> Vec<Task> {
+        let mut tasks = Vec::new();
+        add_task(&mut tasks, "Task 1 +work");
+        add_task(&mut tasks, "Task 2");
+        add_task(&mut tasks, "Task 3");
+        crate::add_subtask(&mut tasks, &[1], "Task 2.1").unwrap();
+        tasks[2].set_status(TaskStatus::Complete);
+        tasks
+    }
+
+    fn lines(tasks: &[Task]) -> Vec<String> {
+        to_editable(tasks)
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn editing_task_text() {
+        let mut tasks = setup_tasks();
+        edit_task(&mut tasks[0], "First task +urgent");
+        assert_eq!(tasks[0].text(), "First task");
+        assert_eq!(tasks[0].tags, BTreeSet::from(["urgent".to_string()]));
+        edit_task(&mut tasks[0], "First task");
+        assert!(tasks[0].tags.is_empty());
+    }
+
+    #[test]
+    fn editing_task_in_editor() {
+        let mut tasks = setup_tasks();
+        assert_eq!(editable_text(&tasks[0]), "Task 1 +work");
+        apply_editable_text(&mut tasks[0], "First task +urgent");
+        assert_eq!(tasks[0].text(), "First task");
+        assert_eq!(tasks[0].tags, BTreeSet::from(["urgent".to_string()]));
+        apply_editable_text(&mut tasks[0], "First task");
+        assert!(tasks[0].tags.is_empty());
+    }
+
+    #[test]
+    fn writing_editable_text() {
+        assert_eq!(
+            lines(&setup_tasks()),
+            vec![
+                "[ ] Task 1 +work #1",
+                "[ ] Task 2 #2",
+                "    [ ] Task 2.1 #4",
+                "[x] Task 3 #3",
+            ]
+        );
+    }
+
+    #[test]
+    fn unchanged_editable_text() {
+        let mut tasks = setup_tasks();
+        let summary = apply_editable(&mut tasks, &to_editable(&setup_tasks()));
+        assert_eq!(summary, EditSummary::default());
+        assert_eq!(tasks, setup_tasks());
+    }
+
+    #[test]
+    fn applying_editable_text() {
+        let mut tasks = setup_tasks();
+        tasks[1].set_priority(Some(crate::Priority::High));
+        let edited = "\
+# A comment
+[x] Task 3 #3
+[x] Task 1 renamed #1
+    [ ] New subtask
+\t[ ] Task 2.1 #4
+New task +home
+";
+        let summary = apply_editable(&mut tasks, edited);
+        assert_eq!(
+            summary,
+            EditSummary {
+                added: 2,
+                changed: 1,
+                removed: 1
+            }
+        );
+        assert_eq!(
+            lines(&tasks),
+            vec![
+                "[x] Task 3 #3",
+                "[x] Task 1 renamed #1",
+                "    [ ] New subtask #0",
+                "    [ ] Task 2.1 #4",
+                "[ ] New task +home #0",
+            ]
+        );
+        assert!(tasks.iter().all(|task| task.priority().is_none()));
+    }
+
+    #[test]
+    fn copied_lines_are_new_tasks() {
+        let mut tasks = setup_tasks();
+        let summary = apply_editable(&mut tasks, "[ ] Task 1 #1\n[ ] Task 1 again #1\n");
+        assert_eq!(summary.added, 1);
+        assert_eq!(lines(&tasks), vec!["[ ] Task 1 #1", "[ ] Task 1 again #0"]);
+    }
+
+    #[test]
+    fn temporary_files() {
+        let file = TempFile::create("Task 1").unwrap();
+        let other = TempFile::create("Task 2").unwrap();
+        assert_ne!(file.path, other.path);
+        assert_eq!(file.read().unwrap(), "Task 1");
+        let path = file.path.clone();
+        drop(file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn unknown_ids_stay_in_text() {
+        let mut tasks = setup_tasks();
+        apply_editable(&mut tasks, "[ ] Fix issue #99\n[ ] Task 1 #1");
+        assert_eq!(tasks[0].text(), "Fix issue #99");
+        assert_eq!(tasks[1].id, 1);
+    }
+
+    #[test]
+    fn editing_tags_in_editable_text() {
+        let mut tasks = setup_tasks();
+        apply_editable(&mut tasks, "[ ] Task 1 +home #1");
+        assert_eq!(tasks[0].tags, BTreeSet::from(["home".to_string()]));
+    }
+
+    #[test]
+    fn too_deep_lines_become_direct_subtasks() {
+        let mut tasks = Vec::new();
+        apply_editable(&mut tasks, "[ ] Parent\n            [ ] Child\n");
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].subtasks()[0].text(), "Child");
+    }
+}
@@ -0,0 +1,103 @@
+// The file formats which tasks can be imported from and exported to
+
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::Utc;
+
+use crate::csv::{self, Column, CSV_SEPARATOR, TSV_SEPARATOR};
+use crate::{clock, ical, legacy, markdown, todotxt, Error, Task};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    Legacy,
+    TodoTxt,
+    Markdown,
+    Ical,
+    Csv,
+    Tsv,
+}
+
+impl Format {
+    // Files ending with .md or .markdown are Markdown checklists, files ending with .ics
+    // are iCalendar files, files ending with .csv or .tsv are spreadsheets and files ending
+    // with .txt are todo.txt files, unless they are in the legacy format (which can only be
+    // told apart by their data)
+    pub fn detect(path: &Path, data: Option<&str>) -> Option<Self> {
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        match extension.map(str::to_lowercase).as_deref() {
+            Some("md" | "markdown") => Some(Format::Markdown),
+            Some("ics" | "ical") => Some(Format::Ical),
+            Some("csv") => Some(Format::Csv),
+            Some("tsv" | "tab") => Some(Format::Tsv),
+            _ if data.is_some_and(legacy::is_legacy) => Some(Format::Legacy),
+            Some("txt") => Some(Format::TodoTxt),
+            _ => None,
+        }
+    }
+
+    // Parses the tasks in the data, the tasks don't have IDs yet
+    pub fn parse(self, data: &str) -> Result<Vec<Task>, Error> {
+        match self {
+            Format::Legacy => legacy::parse_legacy(data),
+            Format::TodoTxt => Ok(todotxt::import_tasks(data)),
+            Format::Markdown => Ok(markdown::parse_tasks(data)),
+            Format::Ical => ical::parse_tasks(data),
+            Format::Csv => csv::parse_tasks(data, CSV_SEPARATOR),
+            Format::Tsv => csv::parse_tasks(data, TSV_SEPARATOR),
+        }
+    }
+
+    // The columns are only used by CSV and TSV
+    pub fn format(self, tasks: &[Task], columns: &[Column]) -> Result<String, Error> {
+        match self {
+            Format::Legacy => Err(Error::ExportLegacyError),
+            Format::TodoTxt => {
+                let mut data = String::new();
+                todotxt::format_tasks(tasks, None, &mut data);
+                Ok(data)
+            }
+            Format::Markdown => Ok(markdown::format_tasks(tasks)),
+            Format::Ical => Ok(ical::format_tasks(tasks, clock::now().with_timezone(&Utc))),
+            Format::Csv => Ok(csv::format_tasks(tasks, columns, CSV_SEPARATOR)),
+            Format::Tsv => Ok(csv::format_tasks(tasks, columns, TSV_SEPARATOR)),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "legacy" => Ok(Format::Legacy),
+            "todotxt" | "todo.txt" | "txt" => Ok(Format::TodoTxt),
+            "markdown" | "md" => Ok(Format::Markdown),
+            "ics" | "ical" | "icalendar" => Ok(Format::Ical),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            _ => Err(Error::ParseFormatError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detecting_formats() {
+        let detect = |path: &str, data| Format::detect(Path::new(path), data);
+        assert_eq!(detect("README.md", None), Some(Format::Markdown));
+        assert_eq!(detect("todo.TXT", Some("(A) Task")), Some(Format::TodoTxt));
+        assert_eq!(
+            detect("todo_tasks.txt", Some("Task`Complete")),
+            Some(Format::Legacy)
+        );
+        assert_eq!(detect("tasks.ics", None), Some(Format::Ical));
+        assert_eq!(detect("tasks.tsv", None), Some(Format::Tsv));
+        assert_eq!(detect("tasks.json", None), None);
+        assert_eq!("markdown".parse(), Ok(Format::Markdown));
+        assert_eq!("yaml".parse::<Format>(), Err(Error::ParseFormatError));
+    }
+}
@@ -0,0 +1,558 @@
+// Reading and writing tasks as the to-dos (VTODO components) of an iCalendar file (RFC 5545),
+// so that they can be moved to calendar apps
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, Timelike, Utc};
+use std::collections::HashMap;
+
+use crate::{Error, Priority, Task, TaskStatus};
+
+const PRODUCT_ID: &str = "-//todo//todo//EN";
+// Longer lines (in bytes) are folded into multiple lines
+const MAX_LINE_LENGTH: usize = 75;
+const DATE_FORMAT: &str = "%Y%m%d";
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+// Categories starting with this are the @contexts of tasks, the others are their tags
+const CONTEXT_PREFIX: char = '@';
+// Date-times in iCalendar only have whole seconds, so the exact times tasks were added and
+// completed at are also kept in these (which other apps ignore)
+const EXACT_CREATED: &str = "X-TODO-CREATED";
+const EXACT_COMPLETED: &str = "X-TODO-COMPLETED";
+// The extensions of tasks from todo.txt files, with one "key:value" in each
+const EXTENSION: &str = "X-TODO-EXT";
+
+// The UID of a task, which is also used by its subtasks for their parent
+fn uid(task: &Task) -> String {
+    format!("todo-{}", task.id)
+}
+
+// Priorities go from 1 (the highest) to 9 (the lowest), 0 means no priority
+fn priority_value(priority: Priority) -> u8 {
+    match priority {
+        Priority::High => 1,
+        Priority::Medium => 5,
+        Priority::Low => 9,
+    }
+}
+
+fn parse_priority(value: &str) -> Option<Priority> {
+    match value.trim().parse::<u8>().ok()? {
+        1..=4 => Some(Priority::High),
+        5 => Some(Priority::Medium),
+        6..=9 => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+// Dates are either like 20261020 or date-times like 20261020T103000Z,
+// only the date of date-times is used
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..8)?, DATE_FORMAT).ok()
+}
+
+// The time a task was added or completed at as a UTC date-time, tasks which only have
+// the date (like the ones from todo.txt files) get midnight instead
+fn date_time_value(time: Option<DateTime<Local>>, date: Option<NaiveDate>) -> Option<String> {
+    match (time, date) {
+        (Some(time), _) => Some(
+            time.with_timezone(&Utc)
+                .format(DATE_TIME_FORMAT)
+                .to_string(),
+        ),
+        (None, Some(date)) => Some(format!("{}T000000Z", date.format(DATE_FORMAT))),
+        (None, None) => None,
+    }
+}
+
+// The time as an RFC 3339 UTC date-time with its fraction of a second, if it has one
+fn exact_value(time: Option<DateTime<Local>>) -> Option<String> {
+    let time = time.filter(|time| time.nanosecond() != 0)?;
+    Some(
+        time.with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+    )
+}
+
+fn parse_exact(value: &str) -> Option<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .ok()
+        .map(|time| time.with_timezone(&Local))
+}
+
+// Parses a UTC date-time like 20261020T103000Z, midnight is left out since it is how
+// the tasks which only have a date are written
+fn parse_date_time(value: &str) -> Option<DateTime<Local>> {
+    let time = NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT).ok()?;
+    (time.time() != NaiveTime::MIN).then(|| time.and_utc().with_timezone(&Local))
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => escaped.extend(['\\', c]),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Unescapes a value, splitting it at the commas which aren't escaped when it is a list
+fn unescape_parts(value: &str, list: bool) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => part.push('\n'),
+                Some(c) => part.push(c),
+                None => {}
+            },
+            ',' if list => parts.push(std::mem::take(&mut part)),
+            c => part.push(c),
+        }
+    }
+    parts.push(part);
+    parts
+}
+
+fn unescape(value: &str) -> String {
+    unescape_parts(value, false).concat()
+}
+
+// Writes a line ending with CRLF, folding it so that no line is longer than MAX_LINE_LENGTH
+fn write_line(out: &mut String, line: &str) {
+    let mut rest = line;
+    let mut length = MAX_LINE_LENGTH;
+    while rest.len() > length {
+        let mut end = length;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.push_str(&rest[..end]);
+        out.push_str("\r\n ");
+        rest = &rest[end..];
+        // The space at the start of the next line is also counted
+        length = MAX_LINE_LENGTH - 1;
+    }
+    out.push_str(rest);
+    out.push_str("\r\n");
+}
+
+// Writes the tasks as a calendar with a to-do for every task, subtasks are related
+// to their parent. The time stamp is the time when the calendar was made
+pub fn format_tasks(tasks: &[Task], stamp: DateTime<Utc>) -> String {
+    fn write_todos(tasks: &[Task], parent: Option<&Task>, stamp: &str, out: &mut String) {
+        for task in tasks {
+            write_line(out, "BEGIN:VTODO");
+            write_line(out, &format!("UID:{}", uid(task)));
+            write_line(out, &format!("DTSTAMP:{stamp}"));
+            write_line(out, &format!("SUMMARY:{}", escape(task.text())));
+            let status = match task.status {
+                TaskStatus::Complete => "COMPLETED",
+                TaskStatus::Incomplete => "NEEDS-ACTION",
+            };
+            write_line(out, &format!("STATUS:{status}"));
+            if let Some(created) = date_time_value(task.created_at, task.created) {
+                write_line(out, &format!("CREATED:{created}"));
+            }
+            if let Some(created) = exact_value(task.created_at) {
+                write_line(out, &format!("{EXACT_CREATED}:{created}"));
+            }
+            if let Some(completed) = date_time_value(task.completed_at, task.completed) {
+                write_line(out, &format!("COMPLETED:{completed}"));
+            }
+            if let Some(completed) = exact_value(task.completed_at) {
+                write_line(out, &format!("{EXACT_COMPLETED}:{completed}"));
+            }
+            if let Some(due) = task.due {
+                write_line(out, &format!("DUE;VALUE=DATE:{}", due.format(DATE_FORMAT)));
+            }
+            if let Some(priority) = task.priority {
+                write_line(out, &format!("PRIORITY:{}", priority_value(priority)));
+            }
+            let contexts = task.contexts.iter().map(|c| format!("{CONTEXT_PREFIX}{c}"));
+            let categories: Vec<String> = task
+                .tags
+                .iter()
+                .cloned()
+                .chain(contexts)
+                .map(|category| escape(&category))
+                .collect();
+            if !categories.is_empty() {
+                write_line(out, &format!("CATEGORIES:{}", categories.join(",")));
+            }
+            for (key, value) in &task.extensions {
+                write_line(
+                    out,
+                    &format!("{EXTENSION}:{}:{}", escape(key), escape(value)),
+                );
+            }
+            if let Some(parent) = parent {
+                write_line(out, &format!("RELATED-TO:{}", uid(parent)));
+            }
+            write_line(out, "END:VTODO");
+            write_todos(&task.subtasks, Some(task), stamp, out);
+        }
+    }
+
+    let mut out = String::new();
+    write_line(&mut out, "BEGIN:VCALENDAR");
+    write_line(&mut out, "VERSION:2.0");
+    write_line(&mut out, &format!("PRODID:{PRODUCT_ID}"));
+    let stamp = stamp.format(DATE_TIME_FORMAT).to_string();
+    write_todos(tasks, None, &stamp, &mut out);
+    write_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+// Joins folded lines back together, along with the number of the line they started on
+fn unfold(data: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (i, line) in data.lines().enumerate() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some((_, last))) => last.push_str(rest),
+            _ if line.trim().is_empty() => {}
+            _ => lines.push((i + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+// Finds the first of the characters which isn't within quotes
+fn find_unquoted(text: &str, pattern: char) -> Option<usize> {
+    let mut quoted = false;
+    text.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        c if c == pattern && !quoted => Some(i),
+        _ => None,
+    })
+}
+
+// The parameters of a property with their uppercase names, like ("VALUE", "DATE")
+type Params<'a> = Vec<(String, &'a str)>;
+
+// Splits a line like "DUE;VALUE=DATE:20261020" into its uppercase name, its parameters
+// (which may have quoted colons in them) like "VALUE=DATE" and its value
+fn parse_property(line: &str) -> Option<(String, Params<'_>, &str)> {
+    let colon = find_unquoted(line, ':')?;
+    let mut rest = &line[..colon];
+    let mut parts = Vec::new();
+    while let Some(semicolon) = find_unquoted(rest, ';') {
+        parts.push(&rest[..semicolon]);
+        rest = &rest[semicolon + 1..];
+    }
+    parts.push(rest);
+
+    let name = parts[0].trim();
+    if name.is_empty() {
+        return None;
+    }
+    let params = parts[1..]
+        .iter()
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.trim().to_uppercase(), value.trim().trim_matches('"')))
+        .collect();
+    Some((name.to_uppercase(), params, &line[colon + 1..]))
+}
+
+// Whether a RELATED-TO property with these parameters is the to-do's parent, it can also be
+// one of its children or siblings (which are found from their own RELATED-TO instead)
+fn is_parent(params: &[(String, &str)]) -> bool {
+    params
+        .iter()
+        .find(|(key, _)| key == "RELTYPE")
+        .is_none_or(|(_, kind)| kind.eq_ignore_ascii_case("PARENT"))
+}
+
+// A to-do read from a calendar along with the UIDs which relate it to its parent, and the
+// exact times which replace the ones of CREATED and COMPLETED
+#[derive(Default)]
+struct Todo {
+    task: Task,
+    uid: Option<String>,
+    parent: Option<String>,
+    created_at: Option<DateTime<Local>>,
+    completed_at: Option<DateTime<Local>>,
+}
+
+impl Todo {
+    fn set(&mut self, name: &str, params: &[(String, &str)], value: &str) {
+        let task = &mut self.task;
+        match name {
+            "SUMMARY" => task.text = unescape(value),
+            "STATUS" if value.eq_ignore_ascii_case("COMPLETED") => {
+                task.status = TaskStatus::Complete;
+            }
+            "UID" => self.uid = Some(value.to_string()),
+            "RELATED-TO" if is_parent(params) => self.parent = Some(value.to_string()),
+            "CREATED" => match parse_date_time(value) {
+                Some(time) => task.set_created_at(Some(time)),
+                None => task.created = parse_date(value),
+            },
+            "COMPLETED" => match parse_date_time(value) {
+                Some(time) => task.set_completed_at(Some(time)),
+                None => task.completed = parse_date(value),
+            },
+            EXACT_CREATED => self.created_at = parse_exact(value),
+            EXACT_COMPLETED => self.completed_at = parse_exact(value),
+            EXTENSION => {
+                let (key, value) = value.split_once(':').unwrap_or((value, ""));
+                task.extensions.push((unescape(key), unescape(value)));
+            }
+            "DUE" => task.due = parse_date(value),
+            "PRIORITY" => task.priority = parse_priority(value),
+            "CATEGORIES" => {
+                for category in unescape_parts(value, true) {
+                    let category = category.trim();
+                    match category.strip_prefix(CONTEXT_PREFIX) {
+                        Some(context) if !context.is_empty() => {
+                            task.contexts.insert(context.to_string());
+                        }
+                        _ if !category.is_empty() => {
+                            task.tags.insert(category.to_string());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn into_task(mut self) -> Task {
+        if let Some(time) = self.created_at {
+            self.task.set_created_at(Some(time));
+        }
+        if let Some(time) = self.completed_at {
+            self.task.set_completed_at(Some(time));
+        }
+        self.task
+    }
+}
+
+// Parses the to-dos of a calendar into tasks, to-dos related to another to-do become its
+// subtasks. The tasks don't have IDs yet (their UIDs are only used for finding the parents,
+// since imported tasks get new IDs) and any other component (like events) is skipped
+pub fn parse_tasks(data: &str) -> Result<Vec<Task>, Error> {
+    let lines = unfold(data);
+    let mut todos: Vec<Todo> = Vec::new();
+    let mut current: Option<Todo> = None;
+    // Components in a to-do (like alarms) have properties which aren't the to-do's
+    let mut depth = 0;
+
+    for (i, (number, line)) in lines.iter().enumerate() {
+        let Some((name, params, value)) = parse_property(line) else {
+            return Err(Error::ParseCalendarError(*number));
+        };
+        if i == 0 && !(name == "BEGIN" && value.eq_ignore_ascii_case("VCALENDAR")) {
+            return Err(Error::ParseCalendarError(*number));
+        }
+        match (name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VTODO") => {
+                current = Some(Todo::default());
+            }
+            ("BEGIN", Some(_)) => depth += 1,
+            ("END", Some(_)) if depth > 0 => depth -= 1,
+            ("END", Some(_)) => todos.extend(current.take()),
+            (name, Some(todo)) if depth == 0 => todo.set(name, &params, value),
+            _ => {}
+        }
+    }
+
+    Ok(build_tree(todos))
+}
+
+// Adds the to-dos to their parents, the ones without a parent in the calendar are the top
+// level tasks
+fn build_tree(todos: Vec<Todo>) -> Vec<Task> {
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for (i, todo) in todos.iter().enumerate() {
+        if let Some(uid) = &todo.uid {
+            index.entry(uid.as_str()).or_insert(i);
+        }
+    }
+    let mut parents: Vec<Option<usize>> = todos
+        .iter()
+        .map(|todo| index.get(todo.parent.as_deref()?).copied())
+        .collect();
+
+    // Parents which would make a to-do its own ancestor are ignored
+    for i in 0..parents.len() {
+        let mut parent = parents[i];
+        let mut steps = 0;
+        while let Some(j) = parent {
+            if j == i || steps > parents.len() {
+                parents[i] = None;
+                break;
+            }
+            parent = parents[j];
+            steps += 1;
+        }
+    }
+
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); todos.len()];
+    let mut top_level = Vec::new();
+    for (i, parent) in parents.iter().enumerate() {
+        match parent {
+            Some(parent) => children[*parent].push(i),
+            None => top_level.push(i),
+        }
+    }
+
+    fn build(i: usize, tasks: &mut [Option<Task>], children: &[Vec<usize>]) -> Task {
+        let mut task = tasks[i].take().unwrap_or_default();
+        task.subtasks = children[i]
+            .iter()
+            .map(|&child| build(child, tasks, children))
+            .collect();
+        task
+    }
+    let mut tasks: Vec<Option<Task>> = todos
+        .into_iter()
+        .map(|todo| Some(todo.into_task()))
+        .collect();
+    top_level
+        .into_iter()
+        .map(|i| build(i, &mut tasks, &children))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamp() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_790_000_000, 0).unwrap()
+    }
+
+    #[test]
+    fn calendars_round_trip() {
+        let mut tasks = Vec::new();
+        let task = crate::add_task(&mut tasks, "Plan the trip; book hotels, flights +travel");
+        task.set_priority(Some(Priority::High));
+        task.set_due(NaiveDate::from_ymd_opt(2026, 11, 2));
+        task.contexts.insert("computer".to_string());
+        // Like a task from a todo.txt file, which only has the date it was added on
+        task.created_at = None;
+        task.created = NaiveDate::from_ymd_opt(2026, 10, 1);
+        task.extensions.push(("rec".to_string(), "1w".to_string()));
+        task.extensions
+            .push(("url".to_string(), "https://example.com/a;b".to_string()));
+        crate::add_subtask(&mut tasks, &[0], "Book the hotel").unwrap();
+        crate::add_subtask(&mut tasks, &[0, 0], "Compare prices").unwrap();
+        crate::add_subtask(&mut tasks, &[0], "Book the flights").unwrap();
+        tasks[0].subtasks[1].set_status(TaskStatus::Complete);
+        let task = crate::add_task(
+            &mut tasks,
+            &"Ünïcödé text which is long enough to be folded".repeat(4),
+        );
+        task.set_priority(Some(Priority::Low));
+
+        let data = format_tasks(&tasks, stamp());
+        assert!(data.contains("X-TODO-EXT:rec:1w\r\n"));
+        assert!(data.lines().all(|line| line.len() <= MAX_LINE_LENGTH + 1));
+        let mut parsed = parse_tasks(&data).unwrap();
+        crate::assign_missing_ids(&mut parsed);
+        assert_eq!(parsed, tasks);
+    }
+
+    #[test]
+    fn keeping_exact_times() {
+        let mut tasks = Vec::new();
+        let task = crate::add_task(&mut tasks, "Task 1");
+        let created = crate::clock::tests::time() + chrono::Duration::nanoseconds(123_456_789);
+        task.set_created_at(Some(created));
+        task.set_status(TaskStatus::Complete);
+        task.set_completed_at(Some(created + chrono::Duration::milliseconds(1500)));
+        crate::add_task(&mut tasks, "Task 2");
+
+        let data = format_tasks(&tasks, stamp());
+        assert!(data.contains("X-TODO-CREATED:2023-09-20T"));
+        assert_eq!(data.matches("X-TODO-").count(), 2);
+        let mut parsed = parse_tasks(&data).unwrap();
+        crate::assign_missing_ids(&mut parsed);
+        assert_eq!(parsed, tasks);
+    }
+
+    #[test]
+    fn imported_tasks_get_new_ids() {
+        let mut tasks = Vec::new();
+        crate::add_task(&mut tasks, "Task 1");
+        crate::add_subtask(&mut tasks, &[0], "Task 2").unwrap();
+        tasks[0].id = 7;
+        tasks[0].subtasks[0].id = 8;
+        let data = format_tasks(&tasks, stamp());
+        assert!(data.contains("UID:todo-7") && data.contains("RELATED-TO:todo-7"));
+
+        let parsed = parse_tasks(&data).unwrap();
+        assert_eq!(parsed[0].id(), 0);
+        assert_eq!(parsed[0].subtasks()[0].id(), 0);
+        assert_eq!(parsed[0].subtasks()[0].text(), "Task 2");
+    }
+
+    #[test]
+    fn formatting_todos() {
+        let mut tasks = Vec::new();
+        let task = crate::add_task(&mut tasks, "Call mom +family");
+        task.set_due(NaiveDate::from_ymd_opt(2026, 10, 20));
+        task.set_priority(Some(Priority::Medium));
+        // The time the task was added at is written in UTC
+        let created = crate::clock::tests::time().with_timezone(&Utc);
+        assert_eq!(
+            format_tasks(&tasks, stamp()),
+            format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//todo//todo//EN\r\nBEGIN:VTODO\r\n\
+                UID:todo-1\r\nDTSTAMP:20260921T141320Z\r\nSUMMARY:Call mom\r\nSTATUS:NEEDS-ACTION\r\n\
+                CREATED:{}\r\nDUE;VALUE=DATE:20261020\r\nPRIORITY:5\r\nCATEGORIES:family\r\n\
+                END:VTODO\r\nEND:VCALENDAR\r\n",
+                created.format(DATE_TIME_FORMAT)
+            )
+        );
+    }
+
+    #[test]
+    fn parsing_calendars() {
+        let data = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nSUMMARY:Meeting\r\n\
+            END:VEVENT\r\nBEGIN:VTODO\r\nUID:b\r\nSUMMARY:Water the plants\\, all of them\r\n\
+            RELATED-TO;RELTYPE=PARENT:a\r\nEND:VTODO\r\nBEGIN:VTODO\r\nUID:a\r\nSUMMARY:Clean\r\n\
+            \x20\x20up the house\r\nSTATUS:IN-PROCESS\r\nPRIORITY:3\r\nDUE;TZID=\"Europe/A:B\":20261020T120000\r\n\
+            BEGIN:VALARM\r\nSUMMARY:Alarm\r\nEND:VALARM\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        let tasks = parse_tasks(data).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].text(), "Clean up the house");
+        assert!(!tasks[0].is_complete());
+        assert_eq!(tasks[0].priority(), Some(Priority::High));
+        assert_eq!(tasks[0].due(), NaiveDate::from_ymd_opt(2026, 10, 20));
+        assert_eq!(
+            tasks[0].subtasks()[0].text(),
+            "Water the plants, all of them"
+        );
+    }
+
+    #[test]
+    fn related_to_types() {
+        let data = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:a\r\nSUMMARY:Clean\r\n\
+            RELATED-TO;RELTYPE=CHILD:b\r\nEND:VTODO\r\nBEGIN:VTODO\r\nUID:b\r\n\
+            SUMMARY:Vacuum\r\nRELATED-TO;RELTYPE=SIBLING:c\r\nRELATED-TO:a\r\nEND:VTODO\r\n\
+            BEGIN:VTODO\r\nUID:c\r\nSUMMARY:Dust\r\nRELATED-TO;RELTYPE=\"parent\":a\r\n\
+            END:VTODO\r\nEND:VCALENDAR\r\n";
+        let tasks = parse_tasks(data).unwrap();
+        assert_eq!(tasks.len(), 1);
+        let texts: Vec<&str> = tasks[0].subtasks().iter().map(|t| t.text()).collect();
+        assert_eq!(texts, vec!["Vacuum", "Dust"]);
+    }
+
+    #[test]
+    fn invalid_calendars() {
+        assert_eq!(
+            parse_tasks("BEGIN:VCALENDAR\nBEGIN:VTODO\nSUMMARY\n"),
+            Err(Error::ParseCalendarError(3))
+        );
+        assert_eq!(parse_tasks("- [ ] Task"), Err(Error::ParseCalendarError(1)));
+    }
+}
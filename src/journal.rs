@@ -0,0 +1,350 @@
+// Journal of the operations which changed the tasks, used to undo and redo them. Its file has
+// an event (like an operation being done or undone) on every line, new events are appended
+// to it and it is only written again once it has many more events than the kept operations
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use crate::lists::{PlacedTask, TaskLists};
+
+// Older operations are dropped from the journal once it has more than this many
+pub const MAX_ENTRIES: usize = 50;
+
+// A task as it was before and after an operation, None when it wasn't there
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct Change {
+    id: u64,
+    before: Option<PlacedTask>,
+    after: Option<PlacedTask>,
+}
+
+// An operation along with the tasks it changed (and the names of the lists when they changed)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Entry {
+    pub time: DateTime<Local>,
+    pub operation: String,
+    changes: Vec<Change>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    names: Option<(BTreeSet<String>, BTreeSet<String>)>,
+}
+
+impl Entry {
+    // Changes the lists to how they were before the operation (or after it)
+    fn apply(&self, lists: &mut TaskLists, before: bool) {
+        let mut tasks = lists.placed_tasks();
+        for change in &self.changes {
+            let placed = if before {
+                &change.before
+            } else {
+                &change.after
+            };
+            match placed {
+                Some(placed) => tasks.insert(change.id, placed.clone()),
+                None => tasks.remove(&change.id),
+            };
+        }
+        let names = match &self.names {
+            Some((names, _)) if before => names.clone(),
+            Some((_, names)) => names.clone(),
+            None => lists.names(),
+        };
+        *lists = TaskLists::from_placed(names, tasks, lists.next_id());
+    }
+}
+
+// A line of the journal file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Event {
+    Done(Entry),
+    Undone,
+    Redone,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Journal {
+    // Operations which can be undone, the most recent one is last
+    undo: Vec<Entry>,
+    // Undone operations which can be redone, the most recently undone one is last
+    redo: Vec<Entry>,
+    // Number of events in the file, and the events which are not in it yet
+    saved: usize,
+    unsaved: Vec<Event>,
+}
+
+impl Journal {
+    // Reads the events of a journal file
+    pub fn parse(data: &str) -> Result<Self, serde_json::Error> {
+        let mut journal = Journal::default();
+        for line in data.lines().filter(|line| !line.trim().is_empty()) {
+            journal.happen(serde_json::from_str(line)?);
+            journal.saved += 1;
+        }
+        Ok(journal)
+    }
+
+    // Changes the operations which can be undone and redone by the event
+    fn happen(&mut self, event: Event) {
+        match event {
+            Event::Done(entry) => {
+                self.undo.push(entry);
+                if self.undo.len() > MAX_ENTRIES {
+                    self.undo.drain(..self.undo.len() - MAX_ENTRIES);
+                }
+                self.redo.clear();
+            }
+            Event::Undone => self.redo.extend(self.undo.pop()),
+            Event::Redone => self.undo.extend(self.redo.pop()),
+        }
+    }
+
+    fn push(&mut self, event: Event) {
+        self.happen(event.clone());
+        self.unsaved.push(event);
+    }
+
+    // Records an operation done at the time, given the tasks from before and after it.
+    // Undone operations can't be redone anymore after this
+    pub fn record(
+        &mut self,
+        operation: &str,
+        time: DateTime<Local>,
+        before: &TaskLists,
+        after: &TaskLists,
+    ) {
+        let (before_tasks, after_tasks) = (before.placed_tasks(), after.placed_tasks());
+        let ids: BTreeSet<u64> = before_tasks
+            .keys()
+            .chain(after_tasks.keys())
+            .copied()
+            .collect();
+        let changes = ids
+            .into_iter()
+            .map(|id| Change {
+                id,
+                before: before_tasks.get(&id).cloned(),
+                after: after_tasks.get(&id).cloned(),
+            })
+            .filter(|change| change.before != change.after)
+            .collect();
+        let names = (before.names(), after.names());
+        let names = (names.0 != names.1).then_some(names);
+        self.push(Event::Done(Entry {
+            time,
+            operation: operation.to_string(),
+            changes,
+            names,
+        }));
+    }
+
+    // Changes the tasks back to how they were before the last operation and returns it
+    pub fn undo(&mut self, lists: &mut TaskLists) -> Option<&Entry> {
+        self.undo.last()?.apply(lists, true);
+        self.push(Event::Undone);
+        self.redo.last()
+    }
+
+    // Does the last undone operation again and returns it
+    pub fn redo(&mut self, lists: &mut TaskLists) -> Option<&Entry> {
+        self.redo.last()?.apply(lists, false);
+        self.push(Event::Redone);
+        self.undo.last()
+    }
+
+    // Returns all operations with the most recent one first, along with whether it was undone
+    // (undone operations are shown first as they came after the ones which were not undone)
+    pub fn history(&self) -> impl Iterator<Item = (&Entry, bool)> {
+        let undone = self.redo.iter().map(|entry| (entry, true));
+        let done = self.undo.iter().rev().map(|entry| (entry, false));
+        undone.chain(done)
+    }
+
+    // Whether there are events which aren't in the file yet
+    pub fn is_changed(&self) -> bool {
+        !self.unsaved.is_empty()
+    }
+
+    // Whether the file should be written again with only the kept operations instead of
+    // appending to it, since most of its events are about dropped operations
+    pub fn needs_rewrite(&self) -> bool {
+        self.saved + self.unsaved.len() > 2 * MAX_ENTRIES
+    }
+
+    // Writes the events which aren't in the file yet, for appending them to it
+    pub fn write_unsaved(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_events(writer, &self.unsaved)
+    }
+
+    // Writes the whole journal, the undone operations are written as done and then undone
+    pub fn write_all(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let done = self.undo.iter().chain(self.redo.iter().rev());
+        let done = done.map(|entry| Event::Done(entry.clone()));
+        let undone = self.redo.iter().map(|_| Event::Undone);
+        write_events(writer, &done.chain(undone).collect::<Vec<_>>())
+    }
+}
+
+fn write_events(writer: &mut dyn Write, events: &[Event]) -> io::Result<()> {
+    for event in events {
+        serde_json::to_writer(&mut *writer, event)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::lists::DEFAULT_LIST;
+
+    fn texts(lists: &TaskLists) -> Vec<&str> {
+        let tasks = lists.get(DEFAULT_LIST).unwrap();
+        tasks.iter().map(|task| task.text()).collect()
+    }
+
+    // Adds a task to the lists and records it in the journal
+    fn add(journal: &mut Journal, lists: &mut TaskLists, text: &str) {
+        let before = lists.clone();
+        lists.add_task(DEFAULT_LIST, text).unwrap();
+        journal.record(&format!("todo add {text}"), clock::now(), &before, lists);
+    }
+
+    fn operations(journal: &Journal) -> Vec<(&str, bool)> {
+        journal
+            .history()
+            .map(|(entry, undone)| (entry.operation.as_str(), undone))
+            .collect()
+    }
+
+    #[test]
+    fn undoing_and_redoing() {
+        let mut journal = Journal::default();
+        let mut lists = TaskLists::default();
+        add(&mut journal, &mut lists, "Task 1");
+        add(&mut journal, &mut lists, "Task 2");
+
+        let undone = journal.undo(&mut lists).unwrap();
+        assert_eq!(undone.operation, "todo add Task 2");
+        assert_eq!(texts(&lists), vec!["Task 1"]);
+        journal.undo(&mut lists).unwrap();
+        assert!(texts(&lists).is_empty());
+        assert!(journal.undo(&mut lists).is_none());
+
+        let redone = journal.redo(&mut lists).unwrap();
+        assert_eq!(redone.operation, "todo add Task 1");
+        journal.redo(&mut lists).unwrap();
+        assert_eq!(texts(&lists), vec!["Task 1", "Task 2"]);
+        assert!(journal.redo(&mut lists).is_none());
+    }
+
+    #[test]
+    fn recording_clears_redo() {
+        let mut journal = Journal::default();
+        let mut lists = TaskLists::default();
+        add(&mut journal, &mut lists, "Task 1");
+        journal.undo(&mut lists).unwrap();
+        add(&mut journal, &mut lists, "Task 2");
+
+        assert!(journal.redo(&mut lists).is_none());
+        assert_eq!(texts(&lists), vec!["Task 2"]);
+    }
+
+    #[test]
+    fn listing_history() {
+        let mut journal = Journal::default();
+        let mut lists = TaskLists::default();
+        add(&mut journal, &mut lists, "Task 1");
+        add(&mut journal, &mut lists, "Task 2");
+        add(&mut journal, &mut lists, "Task 3");
+        journal.undo(&mut lists).unwrap();
+
+        assert_eq!(
+            operations(&journal),
+            vec![
+                ("todo add Task 3", true),
+                ("todo add Task 2", false),
+                ("todo add Task 1", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn journal_keeps_recent_entries() {
+        let mut journal = Journal::default();
+        let mut lists = TaskLists::default();
+        for i in 0..MAX_ENTRIES + 5 {
+            add(&mut journal, &mut lists, &format!("Task {i}"));
+        }
+        assert_eq!(journal.history().count(), MAX_ENTRIES);
+        assert_eq!(
+            journal.history().last().unwrap().0.operation,
+            "todo add Task 5"
+        );
+    }
+
+    #[test]
+    fn entries_keep_only_changed_tasks() {
+        let mut journal = Journal::default();
+        let mut lists = TaskLists::default();
+        for i in 1..=3 {
+            add(&mut journal, &mut lists, &format!("Task {i}"));
+        }
+        lists.add_subtask(DEFAULT_LIST, &[0], "Task 1.1").unwrap();
+        let before = lists.clone();
+        lists.get_mut(DEFAULT_LIST).unwrap().remove(0);
+        journal.record("todo -r 1", clock::now(), &before, &lists);
+        assert_eq!(journal.undo.last().unwrap().changes.len(), 3);
+
+        journal.undo(&mut lists).unwrap();
+        assert_eq!(lists, before);
+        journal.redo(&mut lists).unwrap();
+        assert_eq!(texts(&lists), vec!["Task 2", "Task 3"]);
+    }
+
+    #[test]
+    fn undoing_list_changes() {
+        let mut journal = Journal::default();
+        let mut lists = TaskLists::default();
+        add(&mut journal, &mut lists, "Task 1");
+        let before = lists.clone();
+        lists.create("work").unwrap();
+        lists.add_task("work", "Task 2").unwrap();
+        lists.rename("work", "office").unwrap();
+        let after = lists.clone();
+        journal.record("todo lists new work", clock::now(), &before, &lists);
+
+        // The next ID isn't undone, so the IDs of undone tasks aren't used again
+        journal.undo(&mut lists).unwrap();
+        assert_eq!(lists, before.with_next_id(3));
+        journal.redo(&mut lists).unwrap();
+        assert_eq!(lists, after);
+    }
+
+    #[test]
+    fn appending_and_rewriting_journal() {
+        let mut journal = Journal::default();
+        let mut lists = TaskLists::default();
+        add(&mut journal, &mut lists, "Task 1");
+        add(&mut journal, &mut lists, "Task 2");
+        let mut file = Vec::new();
+        journal.write_unsaved(&mut file).unwrap();
+
+        let mut journal = Journal::parse(&String::from_utf8(file.clone()).unwrap()).unwrap();
+        assert!(!journal.is_changed());
+        add(&mut journal, &mut lists, "Task 3");
+        journal.undo(&mut lists).unwrap();
+        journal.write_unsaved(&mut file).unwrap();
+
+        let appended = Journal::parse(&String::from_utf8(file).unwrap()).unwrap();
+        assert_eq!(operations(&appended), operations(&journal));
+        let mut rewritten = Vec::new();
+        journal.write_all(&mut rewritten).unwrap();
+        let rewritten = Journal::parse(&String::from_utf8(rewritten).unwrap()).unwrap();
+        assert_eq!(operations(&rewritten), operations(&journal));
+        assert_eq!(rewritten.redo, journal.redo);
+        assert!(!rewritten.needs_rewrite());
+    }
+}
@@ -0,0 +1,88 @@
+// Reading the text format used before the tasks were saved as JSON, where every line
+// is the text of a task and its status separated by a backtick like "Buy milk`Complete"
+
+use crate::{Error, Task, TaskStatus};
+
+// The file the tasks were saved in by default and the separator of their text and status
+pub use crate::defaults::{DEFAULT_TASKS_FILE as LEGACY_TASKS_FILE, SEPARATOR};
+
+fn parse_status(status: &str) -> Option<TaskStatus> {
+    match status.trim().to_lowercase().as_str() {
+        "complete" | "true" | "x" => Some(TaskStatus::Complete),
+        "incomplete" | "false" => Some(TaskStatus::Incomplete),
+        _ => None,
+    }
+}
+
+// Checks if the data of a task file is in the legacy format instead of JSON
+pub fn is_legacy(data: &str) -> bool {
+    let mut lines = data
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .peekable();
+    lines.peek().is_some()
+        && lines.all(|line| {
+            line.rsplit_once(SEPARATOR)
+                .is_some_and(|(_, status)| parse_status(status).is_some())
+        })
+}
+
+// Parses the tasks in the legacy format, the tasks don't have IDs yet
+pub fn parse_legacy(data: &str) -> Result<Vec<Task>, Error> {
+    let mut tasks = Vec::new();
+
+    for (i, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let status = line
+            .rsplit_once(SEPARATOR)
+            .and_then(|(text, status)| Some((text, parse_status(status)?)));
+        let Some((text, status)) = status else {
+            return Err(Error::ParseLegacyError(i + 1));
+        };
+
+        let mut task = Task::new(text.trim());
+        task.status = status;
+        tasks.push(task);
+    }
+    Ok(tasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_legacy_tasks() {
+        let data = "Buy milk`Incomplete\nCall `mom` back`Complete\n\n";
+        let tasks = parse_legacy(data).unwrap();
+        let parsed: Vec<(&str, bool)> = tasks
+            .iter()
+            .map(|task| (task.text(), task.is_complete()))
+            .collect();
+        assert_eq!(parsed, vec![("Buy milk", false), ("Call `mom` back", true)]);
+    }
+
+    #[test]
+    fn invalid_legacy_tasks() {
+        assert_eq!(
+            parse_legacy("Task 1`Complete\nTask 2"),
+            Err(Error::ParseLegacyError(2))
+        );
+        assert_eq!(parse_legacy("Task 1`Done"), Err(Error::ParseLegacyError(1)));
+    }
+
+    #[test]
+    fn detecting_legacy_format() {
+        assert!(is_legacy("Task 1`Complete\nTask 2`Incomplete\n"));
+        assert!(!is_legacy(r#"[{"text":"Task 1","status":"Complete"}]"#));
+        assert!(!is_legacy(r#"{"schema_version":3,"lists":{}}"#));
+        assert!(!is_legacy(""));
+        // Every line needs a status after the backtick, unlike todo.txt tasks quoting code
+        assert!(!is_legacy(
+            "(A) Rename `main`\nx 2023-09-20 Fix `cargo test`\n"
+        ));
+        assert!(!is_legacy("Task 1`Complete\nTask 2`\n"));
+    }
+}
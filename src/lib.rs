@@ -1,155 +1,1502 @@
+use chrono::{DateTime, Local, NaiveDate};
 use colored::Colorize;
 use core::fmt;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::{self, Write};
 use std::ops::Range;
+use std::str::FromStr;
 
+pub mod clock;
+pub mod csv;
+pub mod dates;
+mod defaults;
+pub mod edit;
+pub mod formats;
+pub mod ical;
+pub mod journal;
+pub mod legacy;
+pub mod lists;
+pub mod markdown;
 pub mod messages;
+pub mod output;
+pub mod schema;
+pub mod search;
+pub mod sqlite;
+pub mod storage;
+pub mod tags;
+pub mod todotxt;
+pub mod views;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+use dates::{DueFilter, TimeFormat};
+use lists::TaskLists;
+use tags::TagFilter;
+use views::Views;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Task {
+    // IDs are assigned when a task is added, 0 means that the task has no ID yet
+    #[serde(default)]
+    id: u64,
     text: String,
     status: TaskStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    tags: BTreeSet<String>,
+    // The @contexts, key:value extensions and dates of tasks from todo.txt files
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    contexts: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    extensions: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completed: Option<NaiveDate>,
+    // The times the task was added and completed at, along with the dates above (tasks added
+    // by older versions and tasks from todo.txt files only have the dates)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completed_at: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    subtasks: Vec<Task>,
 }
 
 impl Task {
     pub fn new(task: &str) -> Self {
         Self {
             text: task.to_string(),
-            status: TaskStatus::Incomplete,
+            ..Default::default()
         }
     }
 
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // Completing a task also keeps the time it was completed at (from the clock)
     pub fn set_status(&mut self, status: TaskStatus) {
+        if status != self.status {
+            self.set_completed_at(match status {
+                TaskStatus::Complete => Some(clock::now()),
+                TaskStatus::Incomplete => None,
+            });
+        }
         self.status = status;
     }
 
     pub fn is_complete(&self) -> bool {
         self.status == TaskStatus::Complete
     }
+
+    pub fn priority(&self) -> Option<Priority> {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, priority: Option<Priority>) {
+        self.priority = priority;
+    }
+
+    pub fn due(&self) -> Option<NaiveDate> {
+        self.due
+    }
+
+    pub fn created_at(&self) -> Option<DateTime<Local>> {
+        self.created_at
+    }
+
+    pub fn completed_at(&self) -> Option<DateTime<Local>> {
+        self.completed_at
+    }
+
+    // Setting the times also sets the dates
+    fn set_created_at(&mut self, time: Option<DateTime<Local>>) {
+        self.created_at = time;
+        self.created = time.map(|time| time.date_naive());
+    }
+
+    fn set_completed_at(&mut self, time: Option<DateTime<Local>>) {
+        self.completed_at = time;
+        self.completed = time.map(|time| time.date_naive());
+    }
+
+    pub fn set_due(&mut self, due: Option<NaiveDate>) {
+        self.due = due;
+    }
+
+    pub fn tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    pub fn add_tag(&mut self, tag: &str) {
+        self.tags.insert(tag.to_string());
+    }
+
+    // Tags within the text (like in todo.txt files) are also removed from it
+    pub fn remove_tag(&mut self, tag: &str) {
+        self.tags.remove(tag);
+        let word = format!("+{tag}");
+        if self.has_word(&word) {
+            let words = self.text.split_whitespace().filter(|w| *w != word);
+            self.text = words.collect::<Vec<_>>().join(" ");
+        }
+    }
+
+    // Whether the word is in the text, like the +tags and @contexts within the text of tasks
+    // from todo.txt files (which aren't shown again after the text)
+    pub(crate) fn has_word(&self, word: &str) -> bool {
+        self.text.split_whitespace().any(|w| w == word)
+    }
+
+    pub fn contexts(&self) -> &BTreeSet<String> {
+        &self.contexts
+    }
+
+    // Extensions like "key:value" which aren't used by todo, in the order they were in
+    pub fn extensions(&self) -> &[(String, String)] {
+        &self.extensions
+    }
+
+    pub fn subtasks(&self) -> &[Task] {
+        &self.subtasks
+    }
+
+    // Checks if any subtask (or any of their subtasks) is still incomplete
+    pub fn has_incomplete_subtasks(&self) -> bool {
+        self.subtasks
+            .iter()
+            .any(|task| !task.is_complete() || task.has_incomplete_subtasks())
+    }
+
+    // Changes the status of the task along with all of its subtasks
+    pub fn set_status_with_subtasks(&mut self, status: TaskStatus) {
+        self.set_status(status);
+        for task in &mut self.subtasks {
+            task.set_status_with_subtasks(status);
+        }
+    }
+}
+
+// A task displayed with parts of its text highlighted, like the matches of a search
+pub struct HighlightedTask<'a> {
+    task: &'a Task,
+    // Byte ranges of the text in order
+    spans: &'a [Range<usize>],
+}
+
+impl Task {
+    pub fn highlighted<'a>(&'a self, spans: &'a [Range<usize>]) -> HighlightedTask<'a> {
+        HighlightedTask { task: self, spans }
+    }
+}
+
+impl fmt::Display for HighlightedTask<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.task.write(f, self.spans)
+    }
 }
 
 impl fmt::Display for Task {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, &[])
+    }
+}
+
+impl Task {
+    // Writes the task in color, the parts of the text in the ranges are highlighted
+    fn write(&self, f: &mut fmt::Formatter<'_>, highlighted: &[Range<usize>]) -> fmt::Result {
         if self.is_complete() {
-            write!(
-                f,
-                "{}{}{} {}",
-                "[".bold(),
-                "x".red().bold(),
-                "]".bold(),
-                self.text.green().bold()
-            )
+            write!(f, "{}{}{} ", "[".bold(), "x".red().bold(), "]".bold())?;
         } else {
-            write!(f, "{} {}", "[ ]".bold(), self.text.yellow().bold())
+            write!(f, "{} ", "[ ]".bold())?;
         }
+
+        if let Some(priority) = self.priority {
+            let marker = format!("({})", priority.letter());
+            write!(f, "{} ", priority.colorize(&marker).bold())?;
+        }
+
+        let color = |text: &str| match self.priority {
+            _ if self.is_complete() => text.green().bold(),
+            Some(priority) => priority.colorize(text).bold(),
+            None => text.yellow().bold(),
+        };
+        let mut written = 0;
+        for span in highlighted {
+            write!(f, "{}", color(&self.text[written..span.start]))?;
+            write!(f, "{}", color(&self.text[span.clone()]).reversed())?;
+            written = span.end;
+        }
+        write!(f, "{}", color(&self.text[written..]))?;
+
+        let tags = self.tags.iter().map(|tag| format!("+{tag}"));
+        for tag in tags.filter(|tag| !self.has_word(tag)) {
+            write!(f, " {}", tag.bright_blue())?;
+        }
+        let contexts = self.contexts.iter().map(|context| format!("@{context}"));
+        for context in contexts.filter(|context| !self.has_word(context)) {
+            write!(f, " {}", context.bright_magenta())?;
+        }
+
+        // Overdue tasks and tasks due today are highlighted
+        if let Some(due) = self.due {
+            let today = clock::now().date_naive();
+            let due_text = format!("due:{due}");
+            let due_text = if self.is_complete() {
+                due_text.dimmed()
+            } else if due < today {
+                due_text.red().bold()
+            } else if due == today {
+                due_text.magenta().bold()
+            } else {
+                due_text.cyan()
+            };
+            write!(f, " {due_text}")?;
+        }
+
+        for (key, value) in &self.extensions {
+            write!(f, " {}", format!("{key}:{value}").dimmed())?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize, PartialEq)]
 pub enum TaskStatus {
+    #[default]
     Incomplete,
     Complete,
 }
 
+// Variants are ordered from the most to the least important
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+}
+
+impl Priority {
+    // The letter used for the priority, in the style of todo.txt
+    pub fn letter(self) -> char {
+        match self {
+            Priority::High => 'A',
+            Priority::Medium => 'B',
+            Priority::Low => 'C',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Self> {
+        match letter {
+            'A' => Some(Priority::High),
+            'B' => Some(Priority::Medium),
+            'C' => Some(Priority::Low),
+            _ => None,
+        }
+    }
+
+    fn colorize(self, text: &str) -> colored::ColoredString {
+        match self {
+            Priority::High => text.red(),
+            Priority::Medium => text.yellow(),
+            Priority::Low => text.blue(),
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "high" | "h" | "a" => Ok(Priority::High),
+            "medium" | "med" | "m" | "b" => Ok(Priority::Medium),
+            "low" | "l" | "c" => Ok(Priority::Low),
+            _ => Err(Error::ParsePriorityError),
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Priority::High => write!(f, "high"),
+            Priority::Medium => write!(f, "medium"),
+            Priority::Low => write!(f, "low"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Error {
     ParsePatternError,
+    ParsePriorityError,
+    ParseDateError,
+    ParseDueFilterError,
+    ParseSortError,
+    ParseTimeFormatError,
+    ParseTagError,
+    ListNotFound(String),
+    ListExists(String),
+    TaskNotFound(String),
+    ParseTaskPathError(String),
+    EditorError(String),
+    LockTimeout(String),
+    LockError(String),
+    InvalidTaskFile(String),
+    NewerSchema(u64),
+    ParseLegacyError(usize),
+    ImportError(String),
+    ExportError(String),
+    ParseFormatError,
+    ParseOutputError,
+    ParseCalendarError(usize),
+    ParseColumnError(String),
+    MissingTextColumn,
+    ParseCsvError(usize, String),
+    UnknownFormat(Option<String>),
+    ExportLegacyError,
+    ParseBackendError,
+    StorageError(String),
+    StrictSelector(Vec<SelectorIssue>),
+    ParseSelectorError(String, usize, &'static str),
+    RegexError(String),
+    ParseViewsError(usize),
+    ParseQueryError(String),
+    ViewNotFound(String),
+    LegacyTaskFile,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ParsePatternError => write!(f, "{}", messages::PATTERN_PARSE_ERR),
+            Error::ParsePriorityError => write!(f, "{}", messages::PRIORITY_PARSE_ERR),
+            Error::ParseDateError => write!(f, "{}", messages::DATE_PARSE_ERR),
+            Error::ParseDueFilterError => write!(f, "{}", messages::DUE_FILTER_PARSE_ERR),
+            Error::ParseSortError => write!(f, "{}", messages::SORT_PARSE_ERR),
+            Error::ParseTimeFormatError => write!(f, "{}", messages::TIME_FORMAT_PARSE_ERR),
+            Error::ParseTagError => write!(f, "{}", messages::TAG_PARSE_ERR),
+            Error::ListNotFound(name) => write!(f, "{}: '{name}'", messages::LIST_NOT_FOUND_ERR),
+            Error::ListExists(name) => write!(f, "{}: '{name}'", messages::LIST_EXISTS_ERR),
+            Error::TaskNotFound(task) => write!(f, "{}: '{task}'", messages::TASK_NOT_FOUND_ERR),
+            Error::ParseTaskPathError(task) => {
+                write!(f, "{}: '{task}'", messages::TASK_PATH_PARSE_ERR)
+            }
+            Error::EditorError(e) => write!(f, "{}: {e}", messages::EDITOR_ERR),
+            Error::LockTimeout(path) => write!(f, "{}: '{path}'", messages::LOCK_TIMEOUT_ERR),
+            Error::LockError(e) => write!(f, "{}: {e}", messages::LOCK_ERR),
+            Error::InvalidTaskFile(e) => write!(f, "{}: {e}", messages::INVALID_TASK_FILE_ERR),
+            Error::ParseLegacyError(line) => {
+                write!(f, "{} (line {line})", messages::LEGACY_PARSE_ERR)
+            }
+            Error::ImportError(e) => write!(f, "{}: {e}", messages::IMPORT_ERR),
+            Error::ExportError(e) => write!(f, "{}: {e}", messages::EXPORT_ERR),
+            Error::ParseCalendarError(line) => {
+                write!(f, "{} (line {line})", messages::CALENDAR_PARSE_ERR)
+            }
+            Error::ParseColumnError(name) => {
+                write!(f, "{}: '{name}'", messages::COLUMN_PARSE_ERR)
+            }
+            Error::MissingTextColumn => write!(f, "{}", messages::MISSING_TEXT_COLUMN_ERR),
+            Error::ParseCsvError(line, column) => {
+                write!(f, "{} '{column}' (line {line})", messages::CSV_PARSE_ERR)
+            }
+            Error::ParseOutputError => write!(f, "{}", messages::OUTPUT_PARSE_ERR),
+            Error::ParseFormatError => write!(f, "{}", messages::FORMAT_PARSE_ERR),
+            Error::UnknownFormat(Some(path)) => {
+                write!(f, "{}: '{path}'", messages::UNKNOWN_FORMAT_ERR)
+            }
+            Error::UnknownFormat(None) => write!(f, "{}", messages::NO_FORMAT_ERR),
+            Error::ExportLegacyError => write!(f, "{}", messages::EXPORT_LEGACY_ERR),
+            Error::ParseBackendError => write!(f, "{}", messages::BACKEND_PARSE_ERR),
+            Error::StorageError(e) => write!(f, "{}: {e}", messages::STORAGE_ERR),
+            Error::LegacyTaskFile => write!(f, "{}", messages::LEGACY_TASK_FILE_ERR),
+            // The reason is followed by the selector with a caret under the character at fault
+            Error::ParseSelectorError(pattern, column, reason) => write!(
+                f,
+                "{}: {reason}\n  {pattern}\n  {}^",
+                messages::SELECTOR_PARSE_ERR,
+                " ".repeat(*column)
+            ),
+            Error::RegexError(e) => write!(f, "{}: {e}", messages::REGEX_ERR),
+            Error::ParseViewsError(line) => {
+                write!(f, "{} (line {line})", messages::VIEWS_PARSE_ERR)
+            }
+            Error::ParseQueryError(term) => write!(f, "{}: '{term}'", messages::QUERY_PARSE_ERR),
+            Error::ViewNotFound(name) => write!(f, "{}: '{name}'", messages::VIEW_NOT_FOUND_ERR),
+            Error::StrictSelector(issues) => {
+                let issues: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+                write!(f, "{}: {}", messages::STRICT_SELECTOR_ERR, issues.join("; "))
+            }
+            Error::NewerSchema(version) => write!(
+                f,
+                "{} (the file has schema version {version}, this version of todo supports up to {})",
+                messages::NEWER_SCHEMA_ERR,
+                schema::SCHEMA_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// A part of a selector which doesn't select tasks as it seems to, like "9" in a list of four
+// tasks, the part is kept as it was written (or like "3.2" for task IDs selecting a position)
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectorIssue {
+    // None of the tasks are in the list
+    OutOfRange(String),
+    // Some of the tasks of the range are not in the list, so none of them are used
+    PartlyOutOfRange(String),
+    // No task has the ID
+    UnknownId(String),
+    // All or some of the tasks were already selected by an earlier part
+    Duplicate(String),
+}
+
+impl SelectorIssue {
+    pub fn part(&self) -> &str {
+        match self {
+            SelectorIssue::OutOfRange(part)
+            | SelectorIssue::PartlyOutOfRange(part)
+            | SelectorIssue::UnknownId(part)
+            | SelectorIssue::Duplicate(part) => part,
+        }
+    }
+
+    // Whether the tasks of the part are left unchanged, duplicates are still changed (once)
+    pub fn is_ignored(&self) -> bool {
+        !matches!(self, SelectorIssue::Duplicate(_))
+    }
+}
+
+impl fmt::Display for SelectorIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let problem = match self {
+            SelectorIssue::OutOfRange(_) => messages::OUT_OF_RANGE_WARN,
+            SelectorIssue::PartlyOutOfRange(_) => messages::PARTLY_OUT_OF_RANGE_WARN,
+            SelectorIssue::UnknownId(_) => messages::UNKNOWN_ID_WARN,
+            SelectorIssue::Duplicate(_) => messages::DUPLICATE_WARN,
+        };
+        write!(f, "'{}' {problem}", self.part())
+    }
+}
+
+// Checks if all tasks in the range are in the list
+fn is_valid_range(range: &Range<u32>, tasks: &[Task]) -> bool {
+    range.start > 0 && range.start < range.end && (range.end as usize - 1) <= tasks.len()
 }
 
-// Removes all invalid indexes from the Range and converts it into a vector of 0-indexed indexes
-fn validate_range(ranges: Vec<Range<u32>>, tasks: &Vec<Task>) -> Vec<u32> {
+// Removes all invalid ranges and converts the rest into a vector of 0-indexed indexes,
+// an index selected by several ranges is only kept the first time
+fn validate_range(ranges: Vec<Range<u32>>, tasks: &[Task]) -> Vec<u32> {
+    let mut seen = BTreeSet::new();
     ranges
         .into_iter()
-        .filter(|range| {
-            (range.start as usize) <= tasks.len() && ((range.end as usize) - 1) <= tasks.len()
-        })
+        .filter(|range| is_valid_range(range, tasks))
         .flat_map(|range| (range.start - 1)..(range.end - 1))
+        .filter(|&index| seen.insert(index))
+        .collect()
+}
+
+// Returns the ID to be used for the next added task, subtasks are also taken into account
+fn next_id<'a>(tasks: impl IntoIterator<Item = &'a Task>) -> u64 {
+    fn max_id(task: &Task) -> u64 {
+        task.subtasks.iter().map(max_id).fold(task.id, u64::max)
+    }
+    tasks.into_iter().map(max_id).max().unwrap_or(0) + 1
+}
+
+// Gives an ID to every task (and subtask) which doesn't have one yet
+// (like tasks saved by older versions)
+pub fn assign_missing_ids<'a>(tasks: impl IntoIterator<Item = &'a mut Task>) {
+    let tasks: Vec<&mut Task> = tasks.into_iter().collect();
+    let mut next = next_id(tasks.iter().map(|task| &**task));
+    assign_ids_from(tasks, &mut next);
+}
+
+// Gives the IDs starting from the next one to the tasks (and subtasks) which don't have one,
+// the next ID is increased past the given ones
+fn assign_ids_from<'a>(tasks: impl IntoIterator<Item = &'a mut Task>, next: &mut u64) {
+    fn assign(task: &mut Task, next: &mut u64) {
+        if task.id == 0 {
+            task.id = *next;
+            *next += 1;
+        }
+        for subtask in &mut task.subtasks {
+            assign(subtask, next);
+        }
+    }
+
+    for task in tasks {
+        assign(task, next);
+    }
+}
+
+// Adds a new task to the end and returns it so that other fields can be set on it,
+// any +tag in the text is removed from it and added as a tag instead
+// (the time it was added at comes from the clock). The task gets the ID after the highest
+// one of the tasks, so tasks are added through lists::TaskLists (which keeps the next ID
+// even when the task with the highest ID is removed) outside of the crate
+pub(crate) fn add_task<'a>(tasks: &'a mut Vec<Task>, text: &str) -> &'a mut Task {
+    let (text, tags) = tags::extract_tags(text);
+    let mut task = Task::new(&text);
+    task.id = next_id(tasks.iter());
+    task.tags = tags;
+    task.set_created_at(Some(clock::now()));
+    tasks.push(task);
+    tasks.last_mut().unwrap()
+}
+
+// Adds a new subtask to the end of the subtasks of a task, the parent is
+// given as a path of 0-indexed positions like the ones returned by parse_task_path()
+// (see add_task() for its ID)
+pub(crate) fn add_subtask<'a>(
+    tasks: &'a mut Vec<Task>,
+    parent: &[usize],
+    text: &str,
+) -> Result<&'a mut Task, Error> {
+    let id = next_id(tasks.iter());
+    let siblings = subtasks_mut(tasks, parent).ok_or_else(|| {
+        let path: Vec<String> = parent.iter().map(|i| (i + 1).to_string()).collect();
+        Error::TaskNotFound(path.join("."))
+    })?;
+    let task = add_task(siblings, text);
+    task.id = id;
+    Ok(task)
+}
+
+// Returns the subtasks of the task at the path, or the tasks themselves for an empty path
+fn subtasks_mut<'a>(tasks: &'a mut Vec<Task>, path: &[usize]) -> Option<&'a mut Vec<Task>> {
+    let mut current = tasks;
+    for &i in path {
+        current = &mut current.get_mut(i)?.subtasks;
+    }
+    Some(current)
+}
+
+// Returns the task at the path of 0-indexed positions
+pub fn task_at_mut<'a>(tasks: &'a mut [Task], path: &[usize]) -> Option<&'a mut Task> {
+    let (first, rest) = path.split_first()?;
+    let mut task = tasks.get_mut(*first)?;
+    for &i in rest {
+        task = task.subtasks.get_mut(i)?;
+    }
+    Some(task)
+}
+
+// Returns the path of 0-indexed positions leading to the task with the ID
+fn find_task(tasks: &[Task], id: u64) -> Option<Vec<usize>> {
+    for (i, task) in tasks.iter().enumerate() {
+        if task.id == id {
+            return Some(vec![i]);
+        }
+        if let Some(mut path) = find_task(&task.subtasks, id) {
+            path.insert(0, i);
+            return Some(path);
+        }
+    }
+    None
+}
+
+// Applies a change to every task in the provided range
+fn update_tasks(tasks: &mut [Task], ranges: Vec<Range<u32>>, mut change: impl FnMut(&mut Task)) {
+    let indexes = validate_range(ranges, tasks);
+
+    for index in indexes {
+        if let Some(t) = tasks.get_mut(index as usize) {
+            change(t);
+        }
+    }
+}
+
+// Changes the status of specific tasks
+pub fn change_task_status(
+    tasks: &mut [Task],
+    ranges_to_mark: Vec<Range<u32>>,
+    new_status: TaskStatus,
+) {
+    update_tasks(tasks, ranges_to_mark, |t| t.set_status(new_status));
+}
+
+// Marks specific tasks as complete, tasks having incomplete subtasks are only marked
+// (along with all of their subtasks) when cascading, otherwise they are left unchanged
+// and their IDs are returned
+pub fn complete_tasks(tasks: &mut [Task], ranges: Vec<Range<u32>>, cascade: bool) -> Vec<u64> {
+    let mut blocked = Vec::new();
+
+    update_tasks(tasks, ranges, |t| {
+        if !t.has_incomplete_subtasks() {
+            t.set_status(TaskStatus::Complete);
+        } else if cascade {
+            t.set_status_with_subtasks(TaskStatus::Complete);
+        } else {
+            blocked.push(t.id);
+        }
+    });
+    blocked
+}
+
+// Changes the priority of specific tasks, None removes the priority
+pub fn change_task_priority(
+    tasks: &mut [Task],
+    ranges: Vec<Range<u32>>,
+    new_priority: Option<Priority>,
+) {
+    update_tasks(tasks, ranges, |t| t.set_priority(new_priority));
+}
+
+// Changes the due date of specific tasks, None removes the due date
+pub fn change_task_due(tasks: &mut [Task], ranges: Vec<Range<u32>>, new_due: Option<NaiveDate>) {
+    update_tasks(tasks, ranges, |t| t.set_due(new_due));
+}
+
+// Adds and removes tags from specific tasks
+pub fn change_task_tags(tasks: &mut [Task], ranges: Vec<Range<u32>>, changes: &TagFilter) {
+    update_tasks(tasks, ranges, |t| {
+        changes.with.iter().for_each(|tag| t.add_tag(tag));
+        changes.without.iter().for_each(|tag| t.remove_tag(tag));
+    });
+}
+
+// Removes tasks based on the provided range
+pub fn remove_tasks(tasks: &mut Vec<Task>, ranges_to_remove: Vec<Range<u32>>) {
+    let mut indexes = validate_range(ranges_to_remove, tasks);
+    indexes.sort_by(|a, b| b.cmp(a));
+
+    for index in indexes {
+        tasks.remove(index as usize);
+    }
+}
+
+// Removes tasks based on the provided range and returns them in their original order
+fn take_tasks(tasks: &mut Vec<Task>, ranges: Vec<Range<u32>>) -> Vec<Task> {
+    let mut indexes = validate_range(ranges, tasks);
+    indexes.sort();
+
+    let mut taken = Vec::with_capacity(indexes.len());
+    for index in indexes.into_iter().rev() {
+        taken.push(tasks.remove(index as usize));
+    }
+    taken.reverse();
+    taken
+}
+
+// Where tasks are moved to by move_tasks()
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Destination {
+    // The 1-indexed position of the first moved task after moving them
+    To(u32),
+    // After the task at this 1-indexed position (before moving them)
+    After(u32),
+    Top,
+    Bottom,
+}
+
+// Moves tasks to another position keeping their relative order,
+// positions past the end of the list move the tasks to the bottom
+pub fn move_tasks(tasks: &mut Vec<Task>, ranges: Vec<Range<u32>>, destination: Destination) {
+    let indexes = validate_range(ranges.clone(), tasks);
+
+    let index = match destination {
+        Destination::To(pos) => pos.saturating_sub(1) as usize,
+        // Only the tasks before it which are not moved stay in front of the moved tasks
+        Destination::After(pos) => {
+            let pos = (pos as usize).min(tasks.len());
+            pos - indexes.iter().filter(|&&i| (i as usize) < pos).count()
+        }
+        Destination::Top => 0,
+        Destination::Bottom => usize::MAX,
+    };
+
+    let moved = take_tasks(tasks, ranges);
+    let index = index.min(tasks.len());
+    tasks.splice(index..index, moved);
+}
+
+// Removes all tasks (and subtasks) marked complete
+pub fn remove_completed_tasks(tasks: &mut Vec<Task>) {
+    tasks.retain(|task| !task.is_complete());
+    for task in tasks {
+        remove_completed_tasks(&mut task.subtasks);
+    }
+}
+
+// Removes all tasks
+pub fn remove_all(tasks: &mut Vec<Task>) {
+    tasks.clear();
+}
+
+// Displays a list of all tasks, along with the times they were added and completed at
+// when a format for the times is given (the same goes for the other functions displaying tasks)
+pub fn display_tasks(tasks: &[Task], times: Option<TimeFormat>) {
+    if tasks.is_empty() {
+        println!("{}", messages::NO_TASKS_TO_DISPLAY);
+    }
+    for (i, task) in tasks.iter().enumerate() {
+        print_task(i, task, times);
+    }
+}
+
+// What tasks are sorted by when listing them
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SortKey {
+    Text,
+    // Open tasks come before done tasks
+    Status,
+    Created,
+    Due,
+    // The most important tasks come first
+    Priority,
+}
+
+impl SortKey {
+    // Compares the tasks by the key, tasks without a date or priority come last
+    pub fn compare(self, a: &Task, b: &Task) -> std::cmp::Ordering {
+        fn missing_last<T: Ord>(a: Option<T>, b: Option<T>) -> std::cmp::Ordering {
+            (a.is_none(), a).cmp(&(b.is_none(), b))
+        }
+        match self {
+            SortKey::Text => a.text.to_lowercase().cmp(&b.text.to_lowercase()),
+            SortKey::Status => a.is_complete().cmp(&b.is_complete()),
+            // Tasks from todo.txt files only have the date they were added on
+            SortKey::Created => missing_last(
+                a.created.map(|date| (date, a.created_at)),
+                b.created.map(|date| (date, b.created_at)),
+            ),
+            SortKey::Due => missing_last(a.due, b.due),
+            SortKey::Priority => {
+                (a.priority.is_none(), a.priority).cmp(&(b.priority.is_none(), b.priority))
+            }
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(SortKey::Text),
+            "status" => Ok(SortKey::Status),
+            "created" => Ok(SortKey::Created),
+            "due" => Ok(SortKey::Due),
+            "priority" => Ok(SortKey::Priority),
+            _ => Err(Error::ParseSortError),
+        }
+    }
+}
+
+// Options for choosing which tasks are listed and in which order, every filter
+// which is set must match
+#[derive(Debug, Default)]
+pub struct ListOptions {
+    // The same as sorting by priority
+    pub by_priority: bool,
+    pub due: Option<DueFilter>,
+    // Filters for the dates the tasks were added and completed on
+    pub created: Option<DueFilter>,
+    pub completed: Option<DueFilter>,
+    pub tags: TagFilter,
+    pub status: Option<TaskStatus>,
+    pub sort: Option<SortKey>,
+    // Lists the tasks in the opposite order (after sorting them)
+    pub reverse: bool,
+    // The most tasks to list
+    pub limit: Option<usize>,
+}
+
+impl ListOptions {
+    // Whether the task matches all of the filters
+    pub fn matches(&self, task: &Task, today: NaiveDate) -> bool {
+        let matches_date = |filter: Option<DueFilter>, date: Option<NaiveDate>| {
+            filter.is_none_or(|filter| date.is_some_and(|date| filter.matches_date(date, today)))
+        };
+        self.status.is_none_or(|status| task.status == status)
+            && self.due.is_none_or(|due| due.matches(task, today))
+            && matches_date(self.created, task.created)
+            && matches_date(self.completed, task.completed)
+            && self.tags.matches_with_subtasks(task)
+    }
+}
+
+// Selects the tasks to list along with their original 0-indexed positions
+pub fn list_tasks<'a>(
+    tasks: &'a [Task],
+    options: &ListOptions,
+    today: NaiveDate,
+) -> Vec<(usize, &'a Task)> {
+    let mut listed: Vec<(usize, &Task)> = tasks
+        .iter()
+        .enumerate()
+        .filter(|(_, task)| options.matches(task, today))
+        .collect();
+
+    // Tasks which are equal by the key stay in the same order, also when reversing the order
+    let sort = options
+        .sort
+        .or(options.by_priority.then_some(SortKey::Priority));
+    match (sort, options.reverse) {
+        (Some(key), false) => listed.sort_by(|(_, a), (_, b)| key.compare(a, b)),
+        (Some(key), true) => listed.sort_by(|(_, a), (_, b)| key.compare(b, a)),
+        (None, true) => listed.reverse(),
+        (None, false) => {}
+    }
+    if let Some(limit) = options.limit {
+        listed.truncate(limit);
+    }
+    listed
+}
+
+// Displays the tasks selected by list_tasks()
+pub fn display_listed_tasks(listed: &[(usize, &Task)], times: Option<TimeFormat>) {
+    if listed.is_empty() {
+        println!("{}", messages::NO_TASKS_TO_DISPLAY);
+    }
+    for (i, task) in listed {
+        print_task(*i, task, times);
+    }
+}
+
+// Displays the tasks found by search::search() with their positions like "3.2",
+// highlighting the parts of their text which matched
+pub fn display_matches(matches: &[search::SearchMatch], times: Option<TimeFormat>) {
+    if matches.is_empty() {
+        println!("{}", messages::NO_MATCHES);
+    }
+    for found in matches {
+        println!(
+            "{}. {} {}{}",
+            found.index.bold(),
+            found.task.highlighted(&found.spans),
+            format!("#{}", found.task.id).dimmed(),
+            format_times(found.task, times)
+        );
+    }
+}
+
+// Displays every view along with its query
+pub fn display_views(views: &Views) {
+    if views.is_empty() {
+        println!("{}", messages::NO_VIEWS);
+    }
+    for (name, query) in views.iter() {
+        println!("{} = {query}", name.bold());
+    }
+}
+
+// Displays the tasks of every list under the name of the list
+pub fn display_all_lists(
+    lists: &TaskLists,
+    options: &ListOptions,
+    today: NaiveDate,
+    times: Option<TimeFormat>,
+) {
+    for (i, (name, tasks)) in lists.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", name.bold().underline());
+        display_listed_tasks(&list_tasks(tasks, options, today), times);
+    }
+}
+
+// Displays every list along with the number of incomplete and complete tasks in it
+pub fn display_list_counts(lists: &TaskLists) {
+    for (name, tasks) in lists.iter() {
+        let done = tasks.iter().filter(|task| task.is_complete()).count();
+        println!(
+            "{} {} open, {} done",
+            name.bold(),
+            (tasks.len() - done).to_string().yellow(),
+            done.to_string().green()
+        );
+    }
+}
+
+// Displays every tag along with the number of incomplete and complete tasks having it
+pub fn display_tag_counts(tasks: &[Task]) {
+    let counts = tags::tag_counts(tasks);
+    if counts.is_empty() {
+        println!("{}", messages::NO_TAGS_TO_DISPLAY);
+    }
+    for (tag, (open, done)) in counts {
+        println!(
+            "{} {} open, {} done",
+            format!("+{tag}").bright_blue().bold(),
+            open.to_string().yellow(),
+            done.to_string().green()
+        );
+    }
+}
+
+// Displays the recorded operations with the most recent one first
+pub fn display_history(journal: &journal::Journal) {
+    let mut history = journal.history().peekable();
+    if history.peek().is_none() {
+        println!("{}", messages::NO_HISTORY);
+    }
+    for (entry, undone) in history {
+        let time = entry.time.format("%Y-%m-%d %H:%M:%S").to_string();
+        if undone {
+            println!(
+                "{} {} {}",
+                time.dimmed(),
+                entry.operation.dimmed(),
+                "(undone)".dimmed()
+            );
+        } else {
+            println!("{} {}", time.dimmed(), entry.operation);
+        }
+    }
+}
+
+// Displays a single task along with its 1-indexed position and ID
+fn print_task(index: usize, task: &Task, times: Option<TimeFormat>) {
+    print_task_tree(&(index + 1).to_string(), 0, task, times);
+}
+
+// Displays a task followed by its subtasks indented under it, numbered like 3.1, 3.2
+fn print_task_tree(number: &str, depth: usize, task: &Task, times: Option<TimeFormat>) {
+    println!(
+        "{}{}. {} {}{}",
+        "    ".repeat(depth),
+        number.bold(),
+        task,
+        format!("#{}", task.id).dimmed(),
+        format_times(task, times)
+    );
+    for (i, subtask) in task.subtasks.iter().enumerate() {
+        print_task_tree(&format!("{number}.{}", i + 1), depth + 1, subtask, times);
+    }
+}
+
+// The times the task was added and completed at, like " (added 3d ago, done 1h ago)",
+// which is empty without a format or when the task has neither time
+fn format_times(task: &Task, times: Option<TimeFormat>) -> String {
+    let Some(format) = times else {
+        return String::new();
+    };
+    let now = clock::now();
+    let mut parts = Vec::new();
+    if let Some(time) = task.created_at {
+        parts.push(format!("added {}", format.format(time, now)));
+    }
+    if let Some(time) = task.completed_at {
+        parts.push(format!("done {}", format.format(time, now)));
+    }
+    if parts.is_empty() {
+        return String::new();
+    }
+    format!(" {}", format!("({})", parts.join(", ")).dimmed())
+}
+
+// Displays a prompt to the user and returns their input
+pub fn take_input(prompt: &str) -> io::Result<String> {
+    let mut input = String::new();
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    io::stdin().read_line(&mut input)?;
+    Ok(input)
+}
+
+// Parses a user entered pattern like "1-6,13,7-9" into a Vec of Ranges
+pub fn parse_pattern(pattern: &str) -> Result<Vec<Range<u32>>, Error> {
+    let mut tasks = Vec::new();
+
+    for nums in pattern.split(',') {
+        let mut nums_it = nums.split('-').map(|s| s.parse::<u32>());
+
+        let start = match nums_it.next() {
+            Some(Ok(num)) => num,
+            _ => return Err(Error::ParsePatternError),
+        };
+
+        let end = match nums_it.next() {
+            Some(Ok(num)) => num,
+            None => start,
+            Some(Err(_)) => return Err(Error::ParsePatternError),
+        };
+
+        // Tasks are numbered from 1 and ranges can't be reversed
+        if start == 0 || end < start {
+            return Err(Error::ParsePatternError);
+        }
+
+        tasks.push(Range {
+            start,
+            end: end + 1,
+        })
+    }
+    Ok(tasks)
+}
+
+// Tasks chosen by a selector, the ranges are grouped by the path of 0-indexed
+// positions of their parent task (an empty path for tasks which are not subtasks),
+// along with the parts of the selector which don't select tasks as they seem to
+#[derive(Debug, Default, PartialEq)]
+pub struct Selection(BTreeMap<Vec<usize>, Vec<Range<u32>>>, Vec<SelectorIssue>);
+
+impl Selection {
+    // Calls the function with the selected ranges and the tasks they belong to for every parent,
+    // the deepest and last parents come first so that removing tasks doesn't move the tasks
+    // which are still to be handled
+    pub fn apply(self, tasks: &mut Vec<Task>, mut f: impl FnMut(&mut Vec<Task>, Vec<Range<u32>>)) {
+        for (parent, ranges) in self.0.into_iter().rev() {
+            if let Some(siblings) = subtasks_mut(tasks, &parent) {
+                f(siblings, ranges);
+            }
+        }
+    }
+
+    // Returns the IDs of the selected tasks which are in the list
+    pub fn ids(&self, tasks: &[Task]) -> Vec<u64> {
+        let mut ids = Vec::new();
+        for (parent, ranges) in &self.0 {
+            let Some(siblings) = subtasks(tasks, parent) else {
+                continue;
+            };
+            for index in validate_range(ranges.clone(), siblings) {
+                ids.extend(siblings.get(index as usize).map(|task| task.id));
+            }
+        }
+        ids
+    }
+
+    // Returns the parts of the selector which don't select tasks as they seem to, tasks selected
+    // more than once are reported by the later parts
+    pub fn issues(&self) -> &[SelectorIssue] {
+        &self.1
+    }
+
+    // Selects the tasks with the IDs wherever they are now, like after other tasks were removed
+    // (IDs of tasks which aren't in the list anymore are left out)
+    pub fn from_ids(ids: &[u64], tasks: &[Task]) -> Selection {
+        let paths = ids.iter().filter_map(|&id| find_task(tasks, id));
+        Selection::from_paths(paths, Vec::new())
+    }
+
+    // Groups the paths of the selected tasks into ranges for each parent
+    fn from_paths(paths: impl IntoIterator<Item = Vec<usize>>, issues: Vec<SelectorIssue>) -> Self {
+        let mut positions: BTreeMap<Vec<usize>, BTreeSet<u32>> = BTreeMap::new();
+        for mut path in paths {
+            let pos = path.pop().unwrap() as u32 + 1;
+            positions.entry(path).or_default().insert(pos);
+        }
+        let mut selection = Selection(BTreeMap::new(), issues);
+        for (parent, positions) in positions {
+            let mut ranges: Vec<Range<u32>> = Vec::new();
+            for pos in positions {
+                match ranges.last_mut() {
+                    Some(range) if range.end == pos => range.end += 1,
+                    _ => ranges.push(pos..pos + 1),
+                }
+            }
+            selection.0.insert(parent, ranges);
+        }
+        selection
+    }
+}
+
+// Returns the subtasks of the task at the path, or the tasks themselves for an empty path
+fn subtasks<'a>(tasks: &'a [Task], path: &[usize]) -> Option<&'a [Task]> {
+    let mut current = tasks;
+    for &i in path {
+        current = &current.get(i)?.subtasks;
+    }
+    Some(current)
+}
+
+// Parses a path like "3.2" into 0-indexed positions like [2, 1], an empty path stays empty
+fn parse_path(path: &str) -> Option<Vec<usize>> {
+    if path.is_empty() {
+        return Some(Vec::new());
+    }
+    path.split('.')
+        .map(|num| match num.parse::<usize>() {
+            Ok(num) if num > 0 => Some(num - 1),
+            _ => None,
+        })
         .collect()
 }
 
-pub fn add_task(tasks: &mut Vec<Task>, text: &str) {
-    tasks.push(Task::new(text));
-}
+// A position in a list of tasks like "3", or counted from the end like "-2" and "last"
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Position {
+    Number(u32),
+    FromEnd(u32),
+}
+
+impl Position {
+    // The 1-indexed position in a list of this length, if it is in the list
+    fn resolve(self, len: usize) -> Option<u32> {
+        match self {
+            Position::Number(num) => Some(num),
+            Position::FromEnd(num) => (num as usize <= len).then(|| (len + 1) as u32 - num),
+        }
+    }
+}
+
+// The tasks a part of a selector stands for
+#[derive(Debug)]
+enum Target {
+    All,
+    Done,
+    Open,
+    Id(u64),
+    Text(regex::Regex),
+    // The tasks listed by a view like "@today" (which are never subtasks)
+    View(String),
+    // Tasks from the first to the last position among the subtasks of the task at the path
+    // of 0-indexed positions (an empty path for tasks which are not subtasks)
+    Positions(Vec<usize>, Position, Position),
+}
+
+impl Target {
+    // Whether the tasks are written out in the selector, only such tasks are reported when they
+    // were already selected since tasks matching filters like "done" often overlap with others
+    fn is_explicit(&self) -> bool {
+        matches!(self, Target::Id(_) | Target::Positions(..))
+    }
+}
+
+// A part of a selector, the tasks of excluded parts (like "!4") are left out of the selection
+// along with their subtasks
+#[derive(Debug)]
+struct SelectorPart<'a> {
+    text: &'a str,
+    excluded: bool,
+    target: Target,
+}
+
+// Parses selectors with the grammar:
+//   selector := part ("," part)*
+//   part     := "!"? (keyword | "#" number | "@" view | "/" regex "/" "i"? | path? range)
+//   range    := position ("-" position?)?
+//   keyword  := "all" | "done" | "open"
+//   path     := (number ".")*
+//   position := number | "-" number | "last"
+struct SelectorParser<'a> {
+    pattern: &'a str,
+    // Byte offset of the next character
+    offset: usize,
+}
+
+impl<'a> SelectorParser<'a> {
+    fn new(pattern: &'a str) -> Self {
+        SelectorParser { pattern, offset: 0 }
+    }
+
+    // An error pointing at the character at the byte offset
+    fn error_at(&self, offset: usize, reason: &'static str) -> Error {
+        let column = self.pattern[..offset].chars().count();
+        Error::ParseSelectorError(self.pattern.to_string(), column, reason)
+    }
+
+    fn error(&self, reason: &'static str) -> Error {
+        self.error_at(self.offset, reason)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.pattern[self.offset..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.offset += c.len_utf8();
+        }
+        found
+    }
+
+    // Whether the part ends at the next character
+    fn at_part_end(&self) -> bool {
+        matches!(self.peek(), None | Some(','))
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = &self.pattern[self.offset..];
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.offset += len;
+        &rest[..len]
+    }
 
-// Changes the status of specific tasks
-pub fn change_task_status(
-    tasks: &mut Vec<Task>,
-    ranges_to_mark: Vec<Range<u32>>,
-    new_status: TaskStatus,
-) {
-    let indexes = validate_range(ranges_to_mark, tasks);
+    fn parse(mut self) -> Result<Vec<SelectorPart<'a>>, Error> {
+        let mut parts = Vec::new();
+        loop {
+            let start = self.offset;
+            let excluded = self.eat('!');
+            let target = self.target()?;
+            parts.push(SelectorPart {
+                text: &self.pattern[start..self.offset],
+                excluded,
+                target,
+            });
+            if self.peek().is_none() {
+                return Ok(parts);
+            }
+            if !self.eat(',') {
+                return Err(self.error(messages::SELECTOR_EXPECTED_COMMA));
+            }
+        }
+    }
 
-    for index in indexes {
-        if let Some(t) = tasks.get_mut(index as usize) {
-            t.set_status(new_status);
+    fn target(&mut self) -> Result<Target, Error> {
+        let start = self.offset;
+        match self.peek() {
+            Some('#') => {
+                self.offset += 1;
+                let digits = self.take_while(|c| c.is_ascii_digit());
+                let id = digits
+                    .parse()
+                    .map_err(|_| self.error(messages::SELECTOR_EXPECTED_NUMBER))?;
+                Ok(Target::Id(id))
+            }
+            Some('/') => self.regex(),
+            Some('@') => {
+                self.offset += 1;
+                let name = self.take_while(|c| c.is_alphanumeric() || c == '-' || c == '_');
+                if name.is_empty() {
+                    return Err(self.error(messages::SELECTOR_EXPECTED_VIEW));
+                }
+                Ok(Target::View(name.to_string()))
+            }
+            Some(c) if c.is_ascii_alphabetic() => match self.take_while(|c| c.is_alphanumeric()) {
+                "all" => Ok(Target::All),
+                "done" => Ok(Target::Done),
+                "open" => Ok(Target::Open),
+                "last" => {
+                    self.offset = start;
+                    self.positions()
+                }
+                _ => Err(self.error_at(start, messages::SELECTOR_EXPECTED_TASK)),
+            },
+            Some(c) if c.is_ascii_digit() || c == '-' => self.positions(),
+            _ => Err(self.error(messages::SELECTOR_EXPECTED_TASK)),
         }
     }
-}
 
-// Removes tasks based on the provided range
-pub fn remove_tasks(tasks: &mut Vec<Task>, ranges_to_remove: Vec<Range<u32>>) {
-    let mut indexes = validate_range(ranges_to_remove, tasks);
-    indexes.sort_by(|a, b| b.cmp(a));
+    // A regex between slashes (where "\/" stands for a slash), followed by "i" for ignoring case
+    fn regex(&mut self) -> Result<Target, Error> {
+        let start = self.offset;
+        self.offset += 1;
+        let mut regex = String::new();
+        let mut chars = self.pattern[self.offset..].char_indices();
+        loop {
+            match chars.next() {
+                Some((i, '/')) => {
+                    self.offset += i + 1;
+                    break;
+                }
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, '/')) => regex.push('/'),
+                    Some((_, c)) => regex.extend(['\\', c]),
+                    None => regex.push('\\'),
+                },
+                Some((_, c)) => regex.push(c),
+                None => return Err(self.error_at(start, messages::SELECTOR_UNCLOSED_REGEX)),
+            }
+        }
+        let ignore_case = self.eat('i');
+        regex::RegexBuilder::new(&regex)
+            .case_insensitive(ignore_case)
+            .build()
+            .map(Target::Text)
+            .map_err(|_| self.error_at(start, messages::SELECTOR_REGEX_ERR))
+    }
 
-    for index in indexes {
-        tasks.remove(index as usize);
+    fn number(&mut self) -> Result<u32, Error> {
+        let start = self.offset;
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        match digits.parse() {
+            Ok(0) => Err(self.error_at(start, messages::SELECTOR_ZERO_ERR)),
+            Ok(num) => Ok(num),
+            Err(_) => Err(self.error_at(start, messages::SELECTOR_EXPECTED_NUMBER)),
+        }
     }
-}
 
-// Removes all tasks marked complete
-pub fn remove_completed_tasks(tasks: &mut Vec<Task>) {
-    tasks.retain(|task| !task.is_complete());
-}
+    fn position(&mut self) -> Result<Position, Error> {
+        if self.pattern[self.offset..].starts_with("last") {
+            self.offset += "last".len();
+            Ok(Position::FromEnd(1))
+        } else if self.eat('-') {
+            self.number().map(Position::FromEnd)
+        } else {
+            self.number().map(Position::Number)
+        }
+    }
 
-// Removes all tasks
-pub fn remove_all(tasks: &mut Vec<Task>) {
-    tasks.clear();
-}
+    // Positions like "3", "3.2-4", "5-", "-2" or "2.last"
+    fn positions(&mut self) -> Result<Target, Error> {
+        let mut path = Vec::new();
+        let mut first = self.position()?;
+        while let Position::Number(num) = first {
+            if !self.eat('.') {
+                break;
+            }
+            path.push(num as usize - 1);
+            first = self.position()?;
+        }
 
-// Displays a list of all tasks
-pub fn display_tasks(tasks: &Vec<Task>) {
-    if tasks.is_empty() {
-        println!("{}", messages::NO_TASKS_TO_DISPLAY);
+        let last = if !self.eat('-') {
+            first
+        } else if self.at_part_end() {
+            Position::FromEnd(1)
+        } else {
+            let end_start = self.offset;
+            let last = self.position()?;
+            if let (Position::Number(start), Position::Number(end)) = (first, last) {
+                if end < start {
+                    return Err(self.error_at(end_start, messages::SELECTOR_REVERSED_ERR));
+                }
+            }
+            last
+        };
+        Ok(Target::Positions(path, first, last))
     }
+}
+
+// Adds the paths of the tasks (and subtasks) for which the function is true
+fn matching_paths(
+    tasks: &[Task],
+    parent: &mut Vec<usize>,
+    f: &impl Fn(&Task) -> bool,
+    paths: &mut Vec<Vec<usize>>,
+) {
     for (i, task) in tasks.iter().enumerate() {
-        println!("{}. {}", format!("{}", i + 1).bold(), task);
+        parent.push(i);
+        if f(task) {
+            paths.push(parent.clone());
+        }
+        matching_paths(&task.subtasks, parent, f, paths);
+        parent.pop();
     }
 }
 
-// Displays a prompt to the user and returns their input
-pub fn take_input(prompt: &str) -> io::Result<String> {
-    let mut input = String::new();
-    print!("{}", prompt);
-    io::stdout().flush()?;
-    io::stdin().read_line(&mut input)?;
-    Ok(input)
+// Finds the paths of the tasks a part of a selector stands for, or the issue with the part
+// (views are resolved by parse_selector_with_views())
+fn resolve_part(part: &SelectorPart, tasks: &[Task]) -> Result<Vec<Vec<usize>>, SelectorIssue> {
+    let mut paths = Vec::new();
+    let mut find =
+        |f: &dyn Fn(&Task) -> bool| matching_paths(tasks, &mut Vec::new(), &f, &mut paths);
+    match &part.target {
+        Target::All => find(&|_| true),
+        Target::Done => find(&|task| task.is_complete()),
+        Target::Open => find(&|task| !task.is_complete()),
+        Target::Text(regex) => find(&|task| regex.is_match(&task.text)),
+        Target::View(_) => {}
+        Target::Id(id) => match find_task(tasks, *id) {
+            Some(path) => paths.push(path),
+            None => return Err(SelectorIssue::UnknownId(part.text.to_string())),
+        },
+        Target::Positions(parent, first, last) => {
+            let len = subtasks(tasks, parent).map_or(0, |siblings| siblings.len());
+            let first = first.resolve(len).filter(|&first| first as usize <= len);
+            let Some(first) = first else {
+                return Err(SelectorIssue::OutOfRange(part.text.to_string()));
+            };
+            match last.resolve(len) {
+                Some(last) if last >= first && last as usize <= len => {
+                    for pos in first..=last {
+                        paths.push([&parent[..], &[pos as usize - 1]].concat());
+                    }
+                }
+                _ => return Err(SelectorIssue::PartlyOutOfRange(part.text.to_string())),
+            }
+        }
+    }
+    Ok(paths)
 }
 
-// Parses a user entered pattern like "1-6,13,7-9" into a Vec of Ranges
-pub fn parse_pattern(pattern: &str) -> Result<Vec<Range<u32>>, Error> {
-    let mut tasks = Vec::new();
+// Parses a selector like "1-5,8,10-12" into the selected tasks, tasks can be selected by
+// positions like "3", "3.2" (for subtasks), "5-" (up to the last task), "-2" (counted from the
+// end) or "last", by their ID like "#42", by "all", "done" or "open" (along with subtasks) and
+// by a regex matching their text like "/deploy/" (or "/deploy/i" for ignoring case),
+// and parts starting with "!" leave out the tasks (along with their subtasks)
+pub fn parse_selector(pattern: &str, tasks: &[Task]) -> Result<Selection, Error> {
+    let today = clock::now().date_naive();
+    parse_selector_with_views(pattern, tasks, &Views::default(), today)
+}
 
-    for nums in pattern.split(',') {
-        let mut nums_it = nums.split('-').map(|s| s.parse::<u32>());
+// Parses a selector like parse_selector() which can also select the tasks listed by a view like
+// "@today", the dates in the query of the view are relative to the given date
+pub fn parse_selector_with_views(
+    pattern: &str,
+    tasks: &[Task],
+    views: &Views,
+    today: NaiveDate,
+) -> Result<Selection, Error> {
+    let parts = SelectorParser::new(pattern).parse()?;
 
-        let start = match nums_it.next() {
-            Some(Ok(num)) => num,
-            _ => return Err(Error::ParsePatternError),
+    let mut selected: Vec<Vec<usize>> = Vec::new();
+    let mut explicit = BTreeSet::new();
+    let mut excluded = Vec::new();
+    let mut issues = Vec::new();
+    for part in &parts {
+        let paths = match &part.target {
+            Target::View(name) => {
+                let listed = list_tasks(tasks, &views.options(name, today)?, today);
+                Ok(listed.iter().map(|&(i, _)| vec![i]).collect())
+            }
+            _ => resolve_part(part, tasks),
         };
-
-        let end = match nums_it.next() {
-            Some(Ok(num)) => num,
-            None => start,
-            Some(Err(_)) => return Err(Error::ParsePatternError),
+        let paths = match paths {
+            Ok(paths) => paths,
+            Err(issue) => {
+                issues.push(issue);
+                continue;
+            }
         };
-
-        tasks.push(Range {
-            start,
-            end: end + 1,
-        })
+        if part.excluded {
+            excluded.extend(paths);
+            continue;
+        }
+        if part.target.is_explicit() {
+            let mut new = paths.iter().map(|path| explicit.insert(path.clone()));
+            if !new.all(|new| new) {
+                explicit.extend(paths.iter().cloned());
+                issues.push(SelectorIssue::Duplicate(part.text.to_string()));
+            }
+        }
+        selected.extend(paths);
     }
-    Ok(tasks)
+
+    // Only the selected tasks which weren't left out are kept
+    selected.retain(|path| !excluded.iter().any(|excluded| path.starts_with(excluded)));
+    Ok(Selection::from_paths(selected, issues))
+}
+
+// Parses a single task like "3", "3.2" or "#42" into the path of
+// 0-indexed positions leading to it
+pub fn parse_task_path(task: &str, tasks: &[Task]) -> Result<Vec<usize>, Error> {
+    let parse_error = || Error::ParseTaskPathError(task.to_string());
+    let path = match task.strip_prefix('#') {
+        Some(id) => {
+            let id = id.parse::<u64>().map_err(|_| parse_error())?;
+            find_task(tasks, id)
+        }
+        None => {
+            let path = parse_path(task).ok_or_else(parse_error)?;
+            let mut current = tasks;
+            let found = path.iter().all(|&i| match current.get(i) {
+                Some(t) => {
+                    current = &t.subtasks;
+                    true
+                }
+                None => false,
+            });
+            (found && !path.is_empty()).then_some(path)
+        }
+    };
+    path.ok_or_else(|| Error::TaskNotFound(task.to_string()))
 }
 
 #[cfg(test)]
@@ -157,12 +1504,11 @@ mod tests {
     use super::*;
 
     fn setup_stuff() -> Vec<Task> {
-        vec![
-            Task::new("Task 1"),
-            Task::new("Task 2"),
-            Task::new("Task 3"),
-            Task::new("Task 4"),
-        ]
+        let mut tasks = Vec::new();
+        for text in ["Task 1", "Task 2", "Task 3", "Task 4"] {
+            add_task(&mut tasks, text);
+        }
+        tasks
     }
 
     #[test]
@@ -183,9 +1529,17 @@ mod tests {
         assert_eq!(parse_pattern("1-"), Err(Error::ParsePatternError));
     }
 
+    #[test]
+    fn pattern_parser_invalid_ranges() {
+        assert_eq!(parse_pattern("0"), Err(Error::ParsePatternError));
+        assert_eq!(parse_pattern("5-3"), Err(Error::ParsePatternError));
+    }
+
     #[test]
     fn removing_completed_tasks() {
         let mut tasks = setup_stuff();
+        // The tasks were added at the time of the fixed clock
+        let now = clock::tests::time();
         tasks[0].set_status(TaskStatus::Complete);
         tasks[1].set_status(TaskStatus::Complete);
         remove_completed_tasks(&mut tasks);
@@ -194,12 +1548,20 @@ mod tests {
             tasks,
             vec![
                 Task {
+                    id: 3,
                     text: "Task 3".into(),
-                    status: TaskStatus::Incomplete
+                    status: TaskStatus::Incomplete,
+                    created: Some(now.date_naive()),
+                    created_at: Some(now),
+                    ..Default::default()
                 },
                 Task {
+                    id: 4,
                     text: "Task 4".into(),
-                    status: TaskStatus::Incomplete
+                    status: TaskStatus::Incomplete,
+                    created: Some(now.date_naive()),
+                    created_at: Some(now),
+                    ..Default::default()
                 },
             ]
         )
@@ -208,17 +1570,75 @@ mod tests {
     #[test]
     fn removing_selected_tasks() {
         let mut tasks = setup_stuff();
+        // The tasks were added at the time of the fixed clock
+        let now = clock::tests::time();
         let marked = parse_pattern("1-2,4").unwrap();
         remove_tasks(&mut tasks, marked);
         assert_eq!(
             tasks,
             vec![Task {
+                id: 3,
                 text: "Task 3".into(),
-                status: TaskStatus::Incomplete
+                status: TaskStatus::Incomplete,
+                created: Some(now.date_naive()),
+                created_at: Some(now),
+                ..Default::default()
             }]
         );
     }
 
+    fn moved_texts(pattern: &str, destination: Destination) -> Vec<String> {
+        let mut tasks = setup_stuff();
+        add_task(&mut tasks, "Task 5");
+        add_task(&mut tasks, "Task 6");
+        move_tasks(&mut tasks, parse_pattern(pattern).unwrap(), destination);
+        tasks.into_iter().map(|task| task.text).collect()
+    }
+
+    #[test]
+    fn moving_tasks_to_position() {
+        assert_eq!(
+            moved_texts("5", Destination::To(1)),
+            vec!["Task 5", "Task 1", "Task 2", "Task 3", "Task 4", "Task 6"]
+        );
+        assert_eq!(
+            moved_texts("1-2", Destination::To(3)),
+            vec!["Task 3", "Task 4", "Task 1", "Task 2", "Task 5", "Task 6"]
+        );
+        assert_eq!(
+            moved_texts("1", Destination::To(99)),
+            vec!["Task 2", "Task 3", "Task 4", "Task 5", "Task 6", "Task 1"]
+        );
+    }
+
+    #[test]
+    fn moving_tasks_after_task() {
+        assert_eq!(
+            moved_texts("1-2", Destination::After(4)),
+            vec!["Task 3", "Task 4", "Task 1", "Task 2", "Task 5", "Task 6"]
+        );
+        assert_eq!(
+            moved_texts("6", Destination::After(2)),
+            vec!["Task 1", "Task 2", "Task 6", "Task 3", "Task 4", "Task 5"]
+        );
+        assert_eq!(
+            moved_texts("2-3", Destination::After(2)),
+            vec!["Task 1", "Task 2", "Task 3", "Task 4", "Task 5", "Task 6"]
+        );
+    }
+
+    #[test]
+    fn moving_multiple_ranges_keeps_order() {
+        assert_eq!(
+            moved_texts("5,2-3,99", Destination::Top),
+            vec!["Task 2", "Task 3", "Task 5", "Task 1", "Task 4", "Task 6"]
+        );
+        assert_eq!(
+            moved_texts("4,1,1", Destination::Bottom),
+            vec!["Task 2", "Task 3", "Task 5", "Task 6", "Task 1", "Task 4"]
+        );
+    }
+
     #[test]
     fn validating_range() {
         let tasks = setup_stuff();
@@ -231,24 +1651,49 @@ mod tests {
         let mut tasks = setup_stuff();
         let marked = parse_pattern("1-2,4,9-9999").unwrap();
         change_task_status(&mut tasks, marked, TaskStatus::Complete);
+        // The tasks were added and completed at the time of the fixed clock
+        let now = clock::tests::time();
+        let today = Some(now.date_naive());
         assert_eq!(
             tasks,
             vec![
                 Task {
+                    id: 1,
                     text: "Task 1".into(),
-                    status: TaskStatus::Complete
+                    status: TaskStatus::Complete,
+                    completed: today,
+                    completed_at: Some(now),
+                    created: today,
+                    created_at: Some(now),
+                    ..Default::default()
                 },
                 Task {
+                    id: 2,
                     text: "Task 2".into(),
-                    status: TaskStatus::Complete
+                    status: TaskStatus::Complete,
+                    completed: today,
+                    completed_at: Some(now),
+                    created: today,
+                    created_at: Some(now),
+                    ..Default::default()
                 },
                 Task {
+                    id: 3,
                     text: "Task 3".into(),
-                    status: TaskStatus::Incomplete
+                    status: TaskStatus::Incomplete,
+                    created: today,
+                    created_at: Some(now),
+                    ..Default::default()
                 },
                 Task {
+                    id: 4,
                     text: "Task 4".into(),
-                    status: TaskStatus::Complete
+                    status: TaskStatus::Complete,
+                    completed: today,
+                    completed_at: Some(now),
+                    created: today,
+                    created_at: Some(now),
+                    ..Default::default()
                 },
             ]
         );
@@ -263,31 +1708,488 @@ mod tests {
     #[test]
     fn adding_new_task() {
         let mut tasks = setup_stuff();
+        // The tasks were added at the time of the fixed clock
+        let now = clock::tests::time();
         add_task(&mut tasks, "This is a New Task!");
         assert_eq!(
             tasks,
             vec![
                 Task {
+                    id: 1,
                     text: "Task 1".into(),
-                    status: TaskStatus::Incomplete
+                    status: TaskStatus::Incomplete,
+                    created: Some(now.date_naive()),
+                    created_at: Some(now),
+                    ..Default::default()
                 },
                 Task {
+                    id: 2,
                     text: "Task 2".into(),
-                    status: TaskStatus::Incomplete
+                    status: TaskStatus::Incomplete,
+                    created: Some(now.date_naive()),
+                    created_at: Some(now),
+                    ..Default::default()
                 },
                 Task {
+                    id: 3,
                     text: "Task 3".into(),
-                    status: TaskStatus::Incomplete
+                    status: TaskStatus::Incomplete,
+                    created: Some(now.date_naive()),
+                    created_at: Some(now),
+                    ..Default::default()
                 },
                 Task {
+                    id: 4,
                     text: "Task 4".into(),
-                    status: TaskStatus::Incomplete
+                    status: TaskStatus::Incomplete,
+                    created: Some(now.date_naive()),
+                    created_at: Some(now),
+                    ..Default::default()
                 },
                 Task {
+                    id: 5,
                     text: "This is a New Task!".into(),
-                    status: TaskStatus::Incomplete
+                    status: TaskStatus::Incomplete,
+                    created: Some(now.date_naive()),
+                    created_at: Some(now),
+                    ..Default::default()
                 },
             ]
         );
     }
+
+    #[test]
+    fn ids_survive_removal() {
+        let mut tasks = setup_stuff();
+        remove_tasks(&mut tasks, parse_pattern("2").unwrap());
+        add_task(&mut tasks, "Task 5");
+        let ids: Vec<u64> = tasks.iter().map(|task| task.id).collect();
+        assert_eq!(ids, vec![1, 3, 4, 5]);
+
+        // The lists keep the next ID when the task with the highest ID is removed
+        let mut lists: TaskLists = [(lists::DEFAULT_LIST.to_string(), setup_stuff())]
+            .into_iter()
+            .collect();
+        let tasks = lists.get_mut(lists::DEFAULT_LIST).unwrap();
+        remove_tasks(tasks, parse_pattern("4").unwrap());
+        let task = lists.add_task(lists::DEFAULT_LIST, "Task 5").unwrap();
+        assert_eq!(task.id, 5);
+    }
+
+    #[test]
+    fn assigning_missing_ids() {
+        let mut tasks = vec![Task::new("Task 1"), Task::new("Task 2")];
+        tasks[1].id = 7;
+        assign_missing_ids(&mut tasks);
+        assert_eq!(tasks[0].id, 8);
+        assert_eq!(tasks[1].id, 7);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn selector_with_ids() {
+        let mut tasks = setup_stuff();
+        remove_tasks(&mut tasks, parse_pattern("1").unwrap());
+        assert_eq!(
+            parse_selector("#3,1,#99", &tasks),
+            Ok(Selection(
+                BTreeMap::from([(vec![], vec![1..3])]),
+                vec![SelectorIssue::UnknownId("#99".to_string())]
+            ))
+        );
+        assert_eq!(
+            parse_selector("#x", &tasks),
+            Err(Error::ParseSelectorError(
+                "#x".to_string(),
+                1,
+                messages::SELECTOR_EXPECTED_NUMBER
+            ))
+        );
+    }
+
+    #[test]
+    fn parsing_priority() {
+        assert_eq!("high".parse(), Ok(Priority::High));
+        assert_eq!("M".parse(), Ok(Priority::Medium));
+        assert_eq!("c".parse(), Ok(Priority::Low));
+        assert_eq!("urgent".parse::<Priority>(), Err(Error::ParsePriorityError));
+    }
+
+    #[test]
+    fn changing_priority() {
+        let mut tasks = setup_stuff();
+        add_task(&mut tasks, "Task 5").set_priority(Some(Priority::Low));
+        change_task_priority(
+            &mut tasks,
+            parse_pattern("1,3").unwrap(),
+            Some(Priority::High),
+        );
+        change_task_priority(&mut tasks, parse_pattern("5").unwrap(), None);
+        let priorities: Vec<Option<Priority>> = tasks.iter().map(|t| t.priority).collect();
+        assert_eq!(
+            priorities,
+            vec![Some(Priority::High), None, Some(Priority::High), None, None]
+        );
+    }
+
+    #[test]
+    fn sorting_by_priority() {
+        let mut tasks = setup_stuff();
+        tasks[0].set_priority(Some(Priority::Low));
+        tasks[2].set_priority(Some(Priority::High));
+        tasks[3].set_priority(Some(Priority::Low));
+        let options = ListOptions {
+            by_priority: true,
+            ..Default::default()
+        };
+        let today = NaiveDate::from_ymd_opt(2023, 9, 20).unwrap();
+        let order: Vec<usize> = list_tasks(&tasks, &options, today)
+            .iter()
+            .map(|(i, _)| *i)
+            .collect();
+        assert_eq!(order, vec![2, 0, 3, 1]);
+    }
+
+    #[test]
+    fn recording_task_times() {
+        let mut tasks = setup_stuff();
+        let added = clock::now();
+        let later = added + chrono::Duration::days(2);
+        clock::set_clock(clock::FixedClock(later));
+        change_task_status(
+            &mut tasks,
+            parse_pattern("1-2").unwrap(),
+            TaskStatus::Complete,
+        );
+        change_task_status(
+            &mut tasks,
+            parse_pattern("2").unwrap(),
+            TaskStatus::Incomplete,
+        );
+
+        assert_eq!(tasks[0].created_at(), Some(added));
+        assert_eq!(tasks[0].completed_at(), Some(later));
+        assert_eq!((tasks[1].completed_at(), tasks[1].completed), (None, None));
+        assert!(format_times(&tasks[0], Some(TimeFormat::Relative))
+            .contains("(added 2d ago, done just now)"));
+        assert_eq!(format_times(&tasks[0], None), "");
+
+        let today = later.date_naive();
+        let listed = |options: ListOptions| -> Vec<u64> {
+            let listed = list_tasks(&tasks, &options, today);
+            listed.iter().map(|(_, task)| task.id).collect()
+        };
+        let done_today = ListOptions {
+            completed: Some(DueFilter::Today),
+            ..Default::default()
+        };
+        assert_eq!(listed(done_today), vec![1]);
+        let added_today = ListOptions {
+            created: Some(DueFilter::Today),
+            ..Default::default()
+        };
+        assert!(listed(added_today).is_empty());
+    }
+
+    #[test]
+    fn filtering_and_sorting_tasks() {
+        let mut tasks = setup_stuff();
+        let date = |d| NaiveDate::from_ymd_opt(2023, 9, d);
+        tasks[0].text = "b".to_string();
+        tasks[1].text = "A".to_string();
+        tasks[2].text = "c".to_string();
+        tasks[3].text = "a".to_string();
+        tasks[0].set_status(TaskStatus::Complete);
+        tasks[0].created = date(3);
+        tasks[2].created = date(1);
+        tasks[3].created = date(3);
+        tasks[1].set_due(date(20));
+        let today = date(20).unwrap();
+        let listed = |options: ListOptions| -> Vec<usize> {
+            let listed = list_tasks(&tasks, &options, today);
+            listed.iter().map(|(i, _)| *i).collect()
+        };
+
+        let sorted = |sort, reverse| ListOptions {
+            sort: Some(sort),
+            reverse,
+            ..Default::default()
+        };
+        assert_eq!(listed(sorted(SortKey::Text, false)), vec![1, 3, 0, 2]);
+        assert_eq!(listed(sorted(SortKey::Text, true)), vec![2, 0, 1, 3]);
+        assert_eq!(listed(sorted(SortKey::Created, false)), vec![2, 0, 3, 1]);
+        assert_eq!(listed(sorted(SortKey::Created, true)), vec![1, 0, 3, 2]);
+        assert_eq!(listed(sorted(SortKey::Status, false)), vec![1, 2, 3, 0]);
+        assert_eq!(listed(sorted(SortKey::Due, false)), vec![1, 0, 2, 3]);
+
+        let options = ListOptions {
+            status: Some(TaskStatus::Incomplete),
+            sort: Some(SortKey::Text),
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(listed(options), vec![1, 3]);
+        let options = ListOptions {
+            status: Some(TaskStatus::Incomplete),
+            due: Some(DueFilter::Today),
+            ..Default::default()
+        };
+        assert_eq!(listed(options), vec![1]);
+        let options = ListOptions {
+            reverse: true,
+            ..Default::default()
+        };
+        assert_eq!(listed(options), vec![3, 2, 1, 0]);
+        assert_eq!("Due".parse(), Ok(SortKey::Due));
+        assert_eq!("size".parse::<SortKey>(), Err(Error::ParseSortError));
+    }
+
+    #[test]
+    fn priority_round_trip() {
+        let mut tasks = setup_stuff();
+        tasks[1].set_priority(Some(Priority::Medium));
+        let json = serde_json::to_string(&tasks).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Task>>(&json).unwrap(), tasks);
+
+        let old: Vec<Task> =
+            serde_json::from_str(r#"[{"text":"Old","status":"Incomplete"}]"#).unwrap();
+        assert_eq!(old[0].priority, None);
+    }
+
+    #[test]
+    fn listing_tasks_due_today() {
+        let mut tasks = setup_stuff();
+        let today = NaiveDate::from_ymd_opt(2023, 9, 20).unwrap();
+        change_task_due(&mut tasks, parse_pattern("2,4").unwrap(), Some(today));
+        let options = ListOptions {
+            due: Some(DueFilter::Today),
+            ..Default::default()
+        };
+        let listed: Vec<usize> = list_tasks(&tasks, &options, today)
+            .iter()
+            .map(|(i, _)| *i)
+            .collect();
+        assert_eq!(listed, vec![1, 3]);
+    }
+
+    #[test]
+    fn adding_task_with_tags() {
+        let mut tasks = setup_stuff();
+        add_task(&mut tasks, "Deploy +work the app +release");
+        assert_eq!(tasks[4].text, "Deploy the app");
+        assert_eq!(
+            tasks[4].tags,
+            BTreeSet::from(["release".to_string(), "work".to_string()])
+        );
+    }
+
+    #[test]
+    fn changing_tags() {
+        let mut tasks = setup_stuff();
+        add_task(&mut tasks, "Task 5 +work +urgent");
+        let changes = TagFilter::parse(&["+home".into(), "-urgent".into()]).unwrap();
+        change_task_tags(&mut tasks, parse_pattern("1,5").unwrap(), &changes);
+        assert_eq!(tasks[0].tags, BTreeSet::from(["home".to_string()]));
+        assert!(tasks[1].tags.is_empty());
+        assert_eq!(
+            tasks[4].tags,
+            BTreeSet::from(["home".to_string(), "work".to_string()])
+        );
+    }
+
+    fn setup_subtasks() -> Vec<Task> {
+        let mut tasks = setup_stuff();
+        add_subtask(&mut tasks, &[1], "Task 2.1").unwrap();
+        add_subtask(&mut tasks, &[1], "Task 2.2").unwrap();
+        add_subtask(&mut tasks, &[1, 1], "Task 2.2.1").unwrap();
+        tasks
+    }
+
+    fn texts(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|task| task.text()).collect()
+    }
+
+    #[test]
+    fn adding_subtasks() {
+        let mut tasks = setup_subtasks();
+        assert_eq!(texts(tasks[1].subtasks()), vec!["Task 2.1", "Task 2.2"]);
+        assert_eq!(tasks[1].subtasks[1].subtasks[0].id, 7);
+        assert_eq!(
+            add_subtask(&mut tasks, &[8], "Task 9.1"),
+            Err(Error::TaskNotFound("9".into()))
+        );
+    }
+
+    #[test]
+    fn parsing_task_paths() {
+        let tasks = setup_subtasks();
+        assert_eq!(parse_task_path("2.2.1", &tasks), Ok(vec![1, 1, 0]));
+        assert_eq!(parse_task_path("#6", &tasks), Ok(vec![1, 1]));
+        assert_eq!(
+            parse_task_path("2.3", &tasks),
+            Err(Error::TaskNotFound("2.3".into()))
+        );
+        assert_eq!(
+            parse_task_path("2.0", &tasks),
+            Err(Error::ParseTaskPathError("2.0".into()))
+        );
+        assert_eq!(
+            parse_task_path("#x", &tasks),
+            Err(Error::ParseTaskPathError("#x".into()))
+        );
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn selector_with_subtasks() {
+        let tasks = setup_subtasks();
+        assert_eq!(
+            parse_selector("1,2.1-2,#7", &tasks),
+            Ok(Selection(
+                BTreeMap::from([
+                    (vec![], vec![1..2]),
+                    (vec![1], vec![1..3]),
+                    (vec![1, 1], vec![1..2]),
+                ]),
+                Vec::new()
+            ))
+        );
+    }
+
+    #[test]
+    fn ignored_selectors() {
+        let tasks = setup_subtasks();
+        let selection = parse_selector("1,3-9,2.1-5,5.1,#6,#42", &tasks).unwrap();
+        assert_eq!(selection.ids(&tasks), vec![1, 6]);
+        assert_eq!(
+            selection.issues(),
+            vec![
+                SelectorIssue::PartlyOutOfRange("3-9".to_string()),
+                SelectorIssue::PartlyOutOfRange("2.1-5".to_string()),
+                SelectorIssue::OutOfRange("5.1".to_string()),
+                SelectorIssue::UnknownId("#42".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_selectors() {
+        let mut tasks = setup_stuff();
+        let selection = parse_selector("1-3,2,#3,9-12", &tasks).unwrap();
+        assert_eq!(selection.ids(&tasks), vec![1, 2, 3]);
+        assert_eq!(
+            selection.issues(),
+            vec![
+                SelectorIssue::Duplicate("2".to_string()),
+                SelectorIssue::Duplicate("#3".to_string()),
+                SelectorIssue::OutOfRange("9-12".to_string()),
+            ]
+        );
+
+        // Every task is only removed once, leaving the tasks which weren't selected
+        selection.apply(&mut tasks, remove_tasks);
+        assert_eq!(texts(&tasks), vec!["Task 4"]);
+    }
+
+    #[test]
+    fn selector_grammar() {
+        let mut tasks = setup_subtasks();
+        tasks[2].set_status(TaskStatus::Complete);
+        tasks[1].subtasks[1].set_status(TaskStatus::Complete);
+        tasks[3].text = "Deploy the app".to_string();
+        let ids = |pattern| parse_selector(pattern, &tasks).unwrap().ids(&tasks);
+
+        assert_eq!(ids("all"), vec![1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(ids("done"), vec![3, 6]);
+        assert_eq!(ids("open,!2"), vec![1, 4]);
+        assert_eq!(ids("last,-3"), vec![2, 4]);
+        assert_eq!(ids("2-,!3"), vec![2, 4]);
+        assert_eq!(ids("1-4,!2.2,!-2"), vec![1, 2, 4]);
+        assert_eq!(ids("2.last,2.1-"), vec![5, 6]);
+        assert_eq!(ids("/^task 2/i"), vec![2, 5, 6, 7]);
+        assert_eq!(ids("/Deploy|1$/"), vec![1, 4, 5, 7]);
+        assert_eq!(ids(r"/a\/b,c/,1"), vec![1]);
+
+        let selection = parse_selector("-9,1,2-,done", &tasks).unwrap();
+        assert_eq!(
+            selection.issues(),
+            vec![SelectorIssue::OutOfRange("-9".to_string())]
+        );
+    }
+
+    #[test]
+    fn selecting_views() {
+        let mut tasks = setup_stuff();
+        let today = NaiveDate::from_ymd_opt(2023, 9, 20).unwrap();
+        tasks[1].set_priority(Some(Priority::Low));
+        tasks[3].set_priority(Some(Priority::High));
+        tasks[3].set_status(TaskStatus::Complete);
+        let views = Views::parse("top = open sort:priority limit:2").unwrap();
+        let ids = |pattern| {
+            let selection = parse_selector_with_views(pattern, &tasks, &views, today);
+            selection.map(|selection| selection.ids(&tasks))
+        };
+        assert_eq!(ids("@top"), Ok(vec![1, 2]));
+        assert_eq!(ids("@top,!2,4"), Ok(vec![1, 4]));
+        assert_eq!(ids("@later"), Err(Error::ViewNotFound("later".to_string())));
+    }
+
+    #[test]
+    fn invalid_selectors() {
+        let tasks = setup_subtasks();
+        let error = |pattern: &str, column, reason| {
+            let error = Error::ParseSelectorError(pattern.to_string(), column, reason);
+            assert_eq!(parse_selector(pattern, &tasks), Err(error));
+        };
+        error("1,x", 2, messages::SELECTOR_EXPECTED_TASK);
+        error("1-3 5", 3, messages::SELECTOR_EXPECTED_COMMA);
+        error("1,,2", 2, messages::SELECTOR_EXPECTED_TASK);
+        error("2.0", 2, messages::SELECTOR_ZERO_ERR);
+        error("5-3", 2, messages::SELECTOR_REVERSED_ERR);
+        error("1,/(deploy/", 2, messages::SELECTOR_REGEX_ERR);
+        error("/deploy", 0, messages::SELECTOR_UNCLOSED_REGEX);
+        error("-2.1", 2, messages::SELECTOR_EXPECTED_COMMA);
+        error("allx", 0, messages::SELECTOR_EXPECTED_TASK);
+        assert_eq!(
+            parse_selector("1-x", &tasks).unwrap_err().to_string(),
+            format!(
+                "{}: {}\n  1-x\n    ^",
+                messages::SELECTOR_PARSE_ERR,
+                messages::SELECTOR_EXPECTED_NUMBER
+            )
+        );
+    }
+
+    #[test]
+    fn removing_tasks_with_subtasks() {
+        let mut tasks = setup_subtasks();
+        parse_selector("1,2.1,2.2.1", &tasks)
+            .unwrap()
+            .apply(&mut tasks, remove_tasks);
+        assert_eq!(texts(&tasks), vec!["Task 2", "Task 3", "Task 4"]);
+        assert_eq!(texts(tasks[0].subtasks()), vec!["Task 2.2"]);
+        assert!(tasks[0].subtasks[0].subtasks.is_empty());
+    }
+
+    #[test]
+    fn completing_parent_tasks() {
+        let mut tasks = setup_subtasks();
+        let blocked = complete_tasks(&mut tasks, parse_pattern("1-2").unwrap(), false);
+        assert_eq!(blocked, vec![2]);
+        assert!(tasks[0].is_complete());
+        assert!(!tasks[1].is_complete());
+
+        let blocked = complete_tasks(&mut tasks, parse_pattern("2").unwrap(), true);
+        assert!(blocked.is_empty());
+        assert!(tasks[1].is_complete());
+        assert!(!tasks[1].has_incomplete_subtasks());
+    }
+
+    #[test]
+    fn removing_completed_subtasks() {
+        let mut tasks = setup_subtasks();
+        tasks[1].subtasks[0].set_status(TaskStatus::Complete);
+        remove_completed_tasks(&mut tasks);
+        assert_eq!(texts(tasks[1].subtasks()), vec!["Task 2.2"]);
+    }
 }
@@ -0,0 +1,394 @@
+// Named task lists which are all saved together in the same file
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::{Error, Selection, Task};
+
+// The list used when no list is chosen, it always exists even when it has not been created
+pub const DEFAULT_LIST: &str = "default";
+
+// Older files are upgraded to this by crate::schema when loading them
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(from = "StoredLists")]
+pub struct TaskLists {
+    lists: BTreeMap<String, Vec<Task>>,
+    // The ID of the next added task, it only ever increases so that the IDs of removed tasks
+    // are never used again
+    next_id: u64,
+}
+
+#[derive(Deserialize)]
+struct StoredLists {
+    lists: BTreeMap<String, Vec<Task>>,
+    #[serde(default)]
+    next_id: u64,
+}
+
+// The default list is always added, so that lists without it are the same as ones
+// where it is empty, and the next ID is always after the IDs of the tasks
+impl From<StoredLists> for TaskLists {
+    fn from(stored: StoredLists) -> Self {
+        let next_id = crate::next_id(stored.lists.values().flatten()).max(stored.next_id);
+        let mut lists = TaskLists {
+            lists: stored.lists,
+            next_id,
+        };
+        lists.lists.entry(DEFAULT_LIST.to_string()).or_default();
+        lists
+    }
+}
+
+// A task without its subtasks along with where it is, which is in the list (among the subtasks
+// of the parent) right after the task with the ID `after` (or first without it)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlacedTask {
+    list: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    after: Option<u64>,
+    task: Task,
+}
+
+impl FromIterator<(String, Vec<Task>)> for TaskLists {
+    fn from_iter<I: IntoIterator<Item = (String, Vec<Task>)>>(lists: I) -> Self {
+        TaskLists::from(StoredLists {
+            lists: lists.into_iter().collect(),
+            next_id: 0,
+        })
+    }
+}
+
+impl Default for TaskLists {
+    fn default() -> Self {
+        TaskLists::from(StoredLists {
+            lists: BTreeMap::new(),
+            next_id: 0,
+        })
+    }
+}
+
+impl TaskLists {
+    // Keeps the next ID saved along with the tasks, unless the tasks already use it
+    pub fn with_next_id(mut self, next_id: u64) -> Self {
+        self.next_id = self.next_id.max(next_id);
+        self
+    }
+
+    pub fn next_id(&self) -> u64 {
+        self.next_id
+    }
+
+    // Returns all lists (including the default list) sorted by their names
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[Task])> {
+        self.lists
+            .iter()
+            .map(|(name, tasks)| (name.as_str(), tasks.as_slice()))
+    }
+
+    pub fn get(&self, name: &str) -> Result<&[Task], Error> {
+        self.lists
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| Error::ListNotFound(name.to_string()))
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Result<&mut Vec<Task>, Error> {
+        self.lists
+            .get_mut(name)
+            .ok_or_else(|| Error::ListNotFound(name.to_string()))
+    }
+
+    pub fn create(&mut self, name: &str) -> Result<(), Error> {
+        if self.get(name).is_ok() {
+            return Err(Error::ListExists(name.to_string()));
+        }
+        self.lists.insert(name.to_string(), Vec::new());
+        Ok(())
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), Error> {
+        if self.get(new_name).is_ok() {
+            return Err(Error::ListExists(new_name.to_string()));
+        }
+        let tasks = self.delete(name)?;
+        self.lists.insert(new_name.to_string(), tasks);
+        Ok(())
+    }
+
+    // Deletes a list and returns its tasks, the default list is only emptied
+    pub fn delete(&mut self, name: &str) -> Result<Vec<Task>, Error> {
+        if name == DEFAULT_LIST {
+            return self.get_mut(name).map(std::mem::take);
+        }
+        self.lists
+            .remove(name)
+            .ok_or_else(|| Error::ListNotFound(name.to_string()))
+    }
+
+    // Adds a task to a list, IDs are unique across all lists and never used again
+    pub fn add_task(&mut self, list: &str, text: &str) -> Result<&mut Task, Error> {
+        self.add_subtask(list, &[], text)
+    }
+
+    // Adds a subtask under the task at the path (see crate::add_subtask()) in a list
+    pub fn add_subtask(
+        &mut self,
+        list: &str,
+        parent: &[usize],
+        text: &str,
+    ) -> Result<&mut Task, Error> {
+        let id = self.next_id;
+        let tasks = self
+            .lists
+            .get_mut(list)
+            .ok_or_else(|| Error::ListNotFound(list.to_string()))?;
+        let task = crate::add_subtask(tasks, parent, text)?;
+        task.id = id;
+        self.next_id += 1;
+        Ok(task)
+    }
+
+    // Moves tasks to the end of another list keeping their order,
+    // moved subtasks become top level tasks in the other list
+    pub fn move_tasks(&mut self, from: &str, selection: Selection, to: &str) -> Result<(), Error> {
+        self.get(to)?;
+        let mut moved = Vec::new();
+        selection.apply(self.get_mut(from)?, |tasks, ranges| {
+            moved.splice(0..0, crate::take_tasks(tasks, ranges));
+        });
+        self.get_mut(to)?.extend(moved);
+        Ok(())
+    }
+
+    // Adds tasks without IDs (like imported ones) to the end of a list and gives them IDs
+    pub fn import_tasks(&mut self, list: &str, tasks: Vec<Task>) -> Result<(), Error> {
+        self.get_mut(list)?.extend(tasks);
+        self.assign_missing_ids();
+        Ok(())
+    }
+
+    pub fn assign_missing_ids(&mut self) {
+        crate::assign_ids_from(self.lists.values_mut().flatten(), &mut self.next_id);
+    }
+
+    pub fn names(&self) -> BTreeSet<String> {
+        self.lists.keys().cloned().collect()
+    }
+
+    // Every task (and subtask) by its ID, along with where it is
+    pub fn placed_tasks(&self) -> BTreeMap<u64, PlacedTask> {
+        fn place(
+            list: &str,
+            parent: Option<u64>,
+            tasks: &[Task],
+            placed: &mut BTreeMap<u64, PlacedTask>,
+        ) {
+            let mut after = None;
+            for task in tasks {
+                let mut task = task.clone();
+                let subtasks = std::mem::take(&mut task.subtasks);
+                let id = task.id;
+                placed.insert(
+                    id,
+                    PlacedTask {
+                        list: list.to_string(),
+                        parent,
+                        after,
+                        task,
+                    },
+                );
+                place(list, Some(id), &subtasks, placed);
+                after = Some(id);
+            }
+        }
+
+        let mut placed = BTreeMap::new();
+        for (name, tasks) in &self.lists {
+            place(name, None, tasks, &mut placed);
+        }
+        placed
+    }
+
+    // Puts the tasks back together from placed_tasks() into the lists with the names (and the
+    // lists of the tasks). Tasks whose parent isn't there are added as top level tasks and tasks
+    // whose place can't be found are added after their siblings
+    pub fn from_placed(
+        names: BTreeSet<String>,
+        placed: BTreeMap<u64, PlacedTask>,
+        next_id: u64,
+    ) -> Self {
+        fn build(
+            siblings: Vec<PlacedTask>,
+            subtasks: &mut HashMap<u64, Vec<PlacedTask>>,
+        ) -> Vec<Task> {
+            order(siblings)
+                .into_iter()
+                .map(|placed| {
+                    let mut task = placed.task;
+                    let siblings = subtasks.remove(&task.id).unwrap_or_default();
+                    task.subtasks = build(siblings, subtasks);
+                    task
+                })
+                .collect()
+        }
+
+        let ids: BTreeSet<u64> = placed.keys().copied().collect();
+        let mut top_level: BTreeMap<String, Vec<PlacedTask>> =
+            names.into_iter().map(|name| (name, Vec::new())).collect();
+        let mut subtasks: HashMap<u64, Vec<PlacedTask>> = HashMap::new();
+        for placed in placed.into_values() {
+            match placed.parent.filter(|parent| ids.contains(parent)) {
+                Some(parent) => subtasks.entry(parent).or_default().push(placed),
+                None => top_level
+                    .entry(placed.list.clone())
+                    .or_default()
+                    .push(placed),
+            }
+        }
+
+        let lists: Vec<(String, Vec<Task>)> = top_level
+            .into_iter()
+            .map(|(name, siblings)| (name, build(siblings, &mut subtasks)))
+            .collect();
+        lists
+            .into_iter()
+            .collect::<TaskLists>()
+            .with_next_id(next_id)
+    }
+}
+
+// Orders the siblings by following the tasks which are after each other
+fn order(mut siblings: Vec<PlacedTask>) -> Vec<PlacedTask> {
+    let mut ordered = Vec::new();
+    let mut after = None;
+    while let Some(i) = siblings.iter().position(|placed| placed.after == after) {
+        let placed = siblings.remove(i);
+        after = Some(placed.task.id);
+        ordered.push(placed);
+    }
+    ordered.extend(siblings);
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_selector;
+
+    fn setup_lists() -> TaskLists {
+        let mut lists = TaskLists::default();
+        lists.create("work").unwrap();
+        lists.add_task(DEFAULT_LIST, "Task 1").unwrap();
+        lists.add_task("work", "Task 2").unwrap();
+        lists.add_task("work", "Task 3").unwrap();
+        lists.add_task("work", "Task 4").unwrap();
+        lists
+    }
+
+    fn texts(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|task| task.text()).collect()
+    }
+
+    #[test]
+    fn lists_without_default_list() {
+        let lists: TaskLists = serde_json::from_str(r#"{"lists":{}}"#).unwrap();
+        assert_eq!(lists, TaskLists::default());
+        assert_eq!(lists.get(DEFAULT_LIST), Ok(&[][..]));
+    }
+
+    #[test]
+    fn lists_round_trip() {
+        let lists = setup_lists();
+        let json = serde_json::to_string(&lists).unwrap();
+        assert_eq!(serde_json::from_str::<TaskLists>(&json).unwrap(), lists);
+    }
+
+    #[test]
+    fn ids_are_unique_across_lists() {
+        let lists = setup_lists();
+        let ids: Vec<u64> = lists
+            .iter()
+            .flat_map(|(_, tasks)| tasks.iter().map(|task| task.id()))
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn ids_are_never_reused() {
+        let mut lists = setup_lists();
+        lists.get_mut("work").unwrap().pop();
+        let id = lists.add_task("work", "Task 5").unwrap().id();
+        assert_eq!(id, 5);
+
+        let json = serde_json::to_string(&lists).unwrap();
+        let mut lists: TaskLists = serde_json::from_str(&json).unwrap();
+        lists.get_mut("work").unwrap().pop();
+        assert_eq!(lists.add_task("work", "Task 6").unwrap().id(), 6);
+    }
+
+    #[test]
+    fn missing_lists() {
+        let mut lists = setup_lists();
+        assert_eq!(lists.get("home"), Err(Error::ListNotFound("home".into())));
+        assert!(lists.add_task("home", "Task 5").is_err());
+        assert_eq!(lists.get(DEFAULT_LIST).map(texts), Ok(vec!["Task 1"]));
+        assert_eq!(lists.create("work"), Err(Error::ListExists("work".into())));
+    }
+
+    #[test]
+    fn renaming_and_deleting_lists() {
+        let mut lists = setup_lists();
+        lists.rename("work", "office").unwrap();
+        assert!(lists.get("work").is_err());
+        assert_eq!(texts(lists.get("office").unwrap()).len(), 3);
+        assert_eq!(
+            lists.rename("office", DEFAULT_LIST),
+            Err(Error::ListExists(DEFAULT_LIST.into()))
+        );
+
+        lists.delete("office").unwrap();
+        let names: Vec<&str> = lists.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec![DEFAULT_LIST]);
+    }
+
+    #[test]
+    fn importing_tasks() {
+        let mut lists = setup_lists();
+        let tasks = vec![Task::new("Task 5"), Task::new("Task 6")];
+        lists.import_tasks("work", tasks).unwrap();
+        let ids: Vec<u64> = lists.get("work").unwrap().iter().map(Task::id).collect();
+        assert_eq!(ids, vec![2, 3, 4, 5, 6]);
+        assert!(lists.import_tasks("home", Vec::new()).is_err());
+    }
+
+    #[test]
+    fn moving_tasks_between_lists() {
+        let mut lists = setup_lists();
+        let selection = parse_selector("3,1", lists.get("work").unwrap()).unwrap();
+        lists.move_tasks("work", selection, DEFAULT_LIST).unwrap();
+        assert_eq!(
+            texts(lists.get(DEFAULT_LIST).unwrap()),
+            vec!["Task 1", "Task 2", "Task 4"]
+        );
+        assert_eq!(texts(lists.get("work").unwrap()), vec!["Task 3"]);
+        let selection = parse_selector("1", lists.get("work").unwrap()).unwrap();
+        assert!(lists.move_tasks("work", selection, "home").is_err());
+        assert_eq!(texts(lists.get("work").unwrap()), vec!["Task 3"]);
+    }
+
+    #[test]
+    fn moving_subtasks_between_lists() {
+        let mut lists = setup_lists();
+        lists.add_subtask("work", &[0], "Task 2.1").unwrap();
+        lists.add_subtask("work", &[0], "Task 2.2").unwrap();
+        let selection = parse_selector("1.2,3,1.1", lists.get("work").unwrap()).unwrap();
+        lists.move_tasks("work", selection, DEFAULT_LIST).unwrap();
+        assert_eq!(
+            texts(lists.get(DEFAULT_LIST).unwrap()),
+            vec!["Task 1", "Task 4", "Task 2.1", "Task 2.2"]
+        );
+        assert_eq!(lists.get(DEFAULT_LIST).unwrap()[3].id(), 6);
+    }
+}
@@ -1,45 +1,145 @@
-use std::{
-    env, error,
-    fs::{self, File},
-    io::ErrorKind,
-};
+use std::path::{Path, PathBuf};
+use std::{env, error, fs, io::ErrorKind, process::ExitCode, time::Duration};
 
-use todo::Task;
+use todo::lists::{TaskLists, DEFAULT_LIST};
+use todo::storage::{self, Backend, Storage};
+use todo::{journal::Journal, legacy, messages, views::Views};
 mod cli;
 
 pub const FILEPATH_ENV_VAR: &str = "RTODO_FILE_PATH";
 pub const DEFAULT_TASKS_FILE: &str = "todo_tasks.json";
+pub const STORAGE_ENV_VAR: &str = "RTODO_STORAGE";
+pub const LOCK_TIMEOUT_ENV_VAR: &str = "RTODO_LOCK_TIMEOUT";
+pub const VIEWS_ENV_VAR: &str = "RTODO_VIEWS_PATH";
+pub const DEFAULT_LOCK_TIMEOUT_SECS: f64 = 10.0;
 
-fn main() -> Result<(), Box<dyn error::Error>> {
+// The opened storage, the loaded task lists and whether they were upgraded
+type Loaded = (Box<dyn Storage>, TaskLists, bool);
+
+// Opens the storage and tries to load the task lists (upgrading ones saved by older versions)
+// along with whether they were upgraded, or displays why they couldn't be loaded
+fn load_tasks(file_path: &str, backend: Backend) -> Result<Option<Loaded>, Box<dyn error::Error>> {
+    let path = Path::new(file_path);
+    let missing = !path.exists();
+    let load_error = |e| {
+        eprintln!("Error: could not load the file - '{file_path}'\nReason: {e}");
+        Ok(None)
+    };
+    let mut storage = match storage::open(path, backend) {
+        Ok(storage) => storage,
+        Err(e) => return load_error(e),
+    };
+
+    // Tasks saved in the legacy format by older versions may be next to a missing task file
+    if missing {
+        let legacy_path = path.with_file_name(legacy::LEGACY_TASKS_FILE);
+        if let Ok(data) = fs::read_to_string(legacy_path) {
+            if legacy::is_legacy(&data) && cli::confirm_legacy_migration() {
+                return Ok(Some((storage, load_legacy(&data)?, true)));
+            }
+        }
+    }
+
+    match storage.load() {
+        Ok((lists, upgraded)) => Ok(Some((storage, lists, upgraded))),
+        Err(todo::Error::LegacyTaskFile) => {
+            if !cli::confirm_legacy_migration() {
+                return Ok(None);
+            }
+            let lists = load_legacy(&fs::read_to_string(path)?)?;
+            Ok(Some((storage, lists, true)))
+        }
+        Err(e) => load_error(e),
+    }
+}
+
+fn load_legacy(data: &str) -> Result<TaskLists, todo::Error> {
+    let mut lists = TaskLists::default();
+    lists.import_tasks(DEFAULT_LIST, legacy::parse_legacy(data)?)?;
+    Ok(lists)
+}
+
+// Errors are shown after everything else, and make todo exit with a failure
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn error::Error>> {
     let file_path = match env::var(FILEPATH_ENV_VAR) {
         Ok(path) => path,
         Err(_) => DEFAULT_TASKS_FILE.to_string(),
     };
 
-    // Tries to read the JSON file and returns an empty Vec as string if not found
-    let json_data = match fs::read_to_string(&file_path) {
-        Ok(data) => data,
-        Err(e) if e.kind() == ErrorKind::NotFound => "[]".to_string(),
-        Err(e) => return Err(Box::new(e)),
+    // Seconds to wait for other todo commands to finish with the file
+    let lock_timeout = env::var(LOCK_TIMEOUT_ENV_VAR)
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs.parse().ok()?).ok())
+        .unwrap_or(Duration::from_secs_f64(DEFAULT_LOCK_TIMEOUT_SECS));
+
+    // The lock is held until the tasks are saved, so that concurrent commands don't overwrite
+    // each other's changes
+    let _lock = storage::lock(Path::new(&file_path), lock_timeout)?;
+
+    // The storage is chosen by the extension of the file unless it is set
+    let backend = match env::var(STORAGE_ENV_VAR) {
+        Ok(backend) => backend.parse()?,
+        Err(_) => Backend::from_path(Path::new(&file_path)),
     };
 
-    // Tries to deserialize the JSON data into a Vec of tasks and displays errors if any
-    let mut tasks: Vec<Task> = match serde_json::from_str(&json_data) {
-        Ok(t) => t,
-        Err(e) => {
-            if e.is_syntax() {
-                eprintln!("Error: invalid JSON in file - '{file_path}'\nReason: {e}");
-                return Ok(());
-            }
-            return Err(Box::new(e));
-        }
+    let Some((mut storage, mut lists, upgraded)) = load_tasks(&file_path, backend)? else {
+        return Ok(());
     };
+    let loaded = lists.clone();
+    lists.assign_missing_ids();
 
-    if let Err(todo::Error::ParsePatternError) = cli::cli_run(&mut tasks) {
-        eprintln!("{}", todo::messages::PATTERN_PARSE_ERR);
+    // The journal used for undoing changes is saved next to the task file
+    let journal_path = storage::journal_path(Path::new(&file_path));
+    let journal = match fs::read_to_string(&journal_path) {
+        Ok(data) => Journal::parse(&data).ok(),
+        Err(e) if e.kind() == ErrorKind::NotFound => Some(Journal::default()),
+        Err(e) => return Err(Box::new(e)),
+    };
+    // A journal which can't be read (like one saved by an older version) is started again
+    let rewrite_journal = journal.is_none();
+    if rewrite_journal {
+        eprintln!("{}", messages::JOURNAL_LOAD_ERR);
     }
+    let mut journal = journal.unwrap_or_default();
+
+    // The views are kept next to the task file unless their path is set
+    let views_path = match env::var(VIEWS_ENV_VAR) {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => storage::views_path(Path::new(&file_path)),
+    };
+    let views = match fs::read_to_string(&views_path) {
+        Ok(data) => Views::parse(&data).unwrap_or_else(|e| {
+            eprintln!("{}: {e}", messages::VIEWS_LOAD_ERR);
+            Views::default()
+        }),
+        Err(_) => Views::default(),
+    };
 
-    let file = File::create(file_path)?;
-    serde_json::to_writer(file, &tasks)?;
+    // Nothing is saved when the command fails, so the tasks are left as they were
+    cli::cli_run(&mut lists, &mut journal, views)?;
+
+    // Files are only saved when they were changed
+    if upgraded {
+        storage.save(&lists)?;
+    } else {
+        storage.save_changes(&loaded, &lists)?;
+    }
+    if journal.is_changed() {
+        if rewrite_journal || journal.needs_rewrite() {
+            storage::save_atomic(&journal_path, |writer| journal.write_all(writer))?;
+        } else {
+            storage::append(&journal_path, |writer| journal.write_unsaved(writer))?;
+        }
+    }
     Ok(())
 }
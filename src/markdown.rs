@@ -0,0 +1,232 @@
+// Reading and writing tasks as a Markdown checklist like "- [x] Buy milk", where
+// indented items are subtasks of the item above them
+
+use crate::{edit, tags, Task, TaskStatus};
+
+// Spaces used for every level of subtasks in the written checklist
+const INDENT: &str = "  ";
+
+// A checklist item in a line of Markdown
+struct Item<'a> {
+    checked: bool,
+    // Position of the character between the brackets of the checkbox in the line
+    checkbox: usize,
+    text: &'a str,
+}
+
+fn indentation(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+// Parses a line like "  - [ ] Text" (or with *, + or 1. as the list marker)
+fn parse_item(line: &str) -> Option<Item<'_>> {
+    let content = line.trim_start();
+    let rest = match content.strip_prefix(['-', '*', '+']) {
+        Some(rest) => rest,
+        None => {
+            let number = content.trim_start_matches(|c: char| c.is_ascii_digit());
+            if number.len() == content.len() {
+                return None;
+            }
+            number.strip_prefix(['.', ')'])?
+        }
+    };
+    let box_start = rest.strip_prefix(' ')?.trim_start();
+    let text = box_start.strip_prefix('[')?;
+    let mut chars = text.chars();
+    let checked = match chars.next()? {
+        ' ' => false,
+        'x' | 'X' => true,
+        _ => return None,
+    };
+    let text = chars.as_str().strip_prefix(']')?;
+    if !(text.is_empty() || text.starts_with(char::is_whitespace)) {
+        return None;
+    }
+
+    Some(Item {
+        checked,
+        checkbox: line.len() - box_start.len() + 1,
+        text: text.trim(),
+    })
+}
+
+// Returns every line of the Markdown which isn't empty or in a fenced code block,
+// along with its indentation and the checklist item in it
+fn lines(data: &str) -> impl Iterator<Item = (usize, usize, Option<Item<'_>>)> {
+    let mut in_code = false;
+    data.split_inclusive('\n')
+        .enumerate()
+        .filter_map(move |(i, line)| {
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.trim_start().starts_with("```") {
+                in_code = !in_code;
+                return None;
+            }
+            if in_code || line.trim().is_empty() {
+                return None;
+            }
+            Some((i, indentation(line), parse_item(line)))
+        })
+}
+
+// Parses the checklist items of the Markdown into tasks, the tasks don't have IDs yet
+// and any other text is ignored
+pub fn parse_tasks(data: &str) -> Vec<Task> {
+    let mut tasks: Vec<Task> = Vec::new();
+    // The indentation and position of the items which the next items may be subtasks of
+    let mut parents: Vec<(usize, usize)> = Vec::new();
+
+    for (_, indent, item) in lines(data) {
+        // Other lines end the items which they aren't indented under
+        while parents.last().is_some_and(|&(parent, _)| parent >= indent) {
+            parents.pop();
+        }
+        let Some(item) = item.filter(|item| !item.text.is_empty()) else {
+            continue;
+        };
+        let mut siblings = &mut tasks;
+        for &(_, i) in &parents {
+            siblings = &mut siblings[i].subtasks;
+        }
+
+        let (text, tags) = tags::extract_tags(item.text);
+        let mut task = Task::new(&text);
+        task.tags = tags;
+        if item.checked {
+            task.status = TaskStatus::Complete;
+        }
+        siblings.push(task);
+        parents.push((indent, siblings.len() - 1));
+    }
+    tasks
+}
+
+// Writes the tasks as a checklist, with subtasks indented under their parent
+pub fn format_tasks(tasks: &[Task]) -> String {
+    fn write_tasks(tasks: &[Task], depth: usize, out: &mut String) {
+        for task in tasks {
+            let check = if task.is_complete() { "x" } else { " " };
+            out.push_str(&format!(
+                "{}- [{check}] {}\n",
+                INDENT.repeat(depth),
+                edit::editable_text(task)
+            ));
+            write_tasks(&task.subtasks, depth + 1, out);
+        }
+    }
+
+    let mut out = String::new();
+    write_tasks(tasks, 0, &mut out);
+    out
+}
+
+// Checks or unchecks the items of an existing checklist to match the status of the tasks
+// with the same text, leaving everything else in the Markdown unchanged. Returns the
+// changed Markdown along with the number of changed items
+pub fn sync(data: &str, tasks: &[Task]) -> (String, usize) {
+    fn flatten<'a>(tasks: &'a [Task], out: &mut Vec<Option<&'a Task>>) {
+        for task in tasks {
+            out.push(Some(task));
+            flatten(&task.subtasks, out);
+        }
+    }
+    // Every task is only used for one item, so that items with the same text are
+    // matched to tasks in the same order
+    let mut unmatched = Vec::new();
+    flatten(tasks, &mut unmatched);
+
+    let mut synced: Vec<String> = data.split_inclusive('\n').map(String::from).collect();
+    let mut changed = 0;
+    for (i, item) in lines(data).filter_map(|(i, _, item)| Some((i, item?))) {
+        let (text, _) = tags::extract_tags(item.text);
+        let task = unmatched
+            .iter_mut()
+            .find(|task| task.is_some_and(|task| task.text() == text))
+            .and_then(Option::take);
+        let Some(task) = task else { continue };
+
+        if task.is_complete() != item.checked {
+            let check = if task.is_complete() { "x" } else { " " };
+            synced[i].replace_range(item.checkbox..item.checkbox + 1, check);
+            changed += 1;
+        }
+    }
+    (synced.concat(), changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(tasks: &[Task]) -> Vec<(&str, bool, usize)> {
+        tasks
+            .iter()
+            .map(|task| (task.text(), task.is_complete(), task.subtasks().len()))
+            .collect()
+    }
+
+    #[test]
+    fn parsing_checklists() {
+        let data = "\
+# Release
+
+Some text with - [ ] in it.
+
+- [x] Write the changelog +docs
+- [ ] Publish
+  - [X] Build
+    * [ ] Upload
+  1. [ ] Announce
+- Not a task
+\t- [ ] Tabbed
+```
+- [ ] In a code block
+```
++ [ ] Last
+";
+        let tasks = parse_tasks(data);
+        assert_eq!(
+            parsed(&tasks),
+            vec![
+                ("Write the changelog", true, 0),
+                ("Publish", false, 2),
+                ("Tabbed", false, 0),
+                ("Last", false, 0),
+            ]
+        );
+        assert!(tasks[0].tags().contains("docs"));
+        assert_eq!(
+            parsed(tasks[1].subtasks()),
+            vec![("Build", true, 1), ("Announce", false, 0)]
+        );
+        assert_eq!(
+            parsed(tasks[1].subtasks()[0].subtasks()),
+            vec![("Upload", false, 0)]
+        );
+    }
+
+    #[test]
+    fn checklists_round_trip() {
+        let data = "- [x] Task 1 +work\n  - [ ] Task 1.1\n    - [x] Task 1.1.1\n- [ ] Task 2\n";
+        assert_eq!(format_tasks(&parse_tasks(data)), data);
+    }
+
+    #[test]
+    fn syncing_checklists() {
+        let mut tasks = parse_tasks("- [ ] Task 1\n- [ ] Task 2\n  - [ ] Task 2.1\n");
+        tasks[0].status = TaskStatus::Complete;
+        tasks[1].subtasks[0].status = TaskStatus::Complete;
+
+        let data = "Intro\r\n\r\n* [x] Task 2 +work\r\n    - [ ] Task 2.1\r\n- [ ] Task 1\r\n- [ ] Task 1\r\n- [x] Other\r\n";
+        let (synced, changed) = sync(data, &tasks);
+        assert_eq!(
+            synced,
+            "Intro\r\n\r\n* [ ] Task 2 +work\r\n    - [x] Task 2.1\r\n- [x] Task 1\r\n- [ ] Task 1\r\n- [x] Other\r\n"
+        );
+        assert_eq!(changed, 3);
+    }
+}
@@ -13,4 +13,116 @@ pub const TASK_ADDED: &str = "Task(s) Added";
 pub const PATTERN_PARSE_ERR: &str = 
         "invalid arguments: make sure that they are in the form 1-5,8,10-12 (without spaces) if marking multiple options";
 
+pub const PRIORITY_PARSE_ERR: &str =
+    "invalid priority: use one of high, medium or low (or A, B, C)";
+pub const PRIORITY_CHANGED: &str = "Priority Changed";
+
+pub const DATE_PARSE_ERR: &str =
+    "invalid date: use a date like 2023-10-20, today, tomorrow, a weekday like fri, an offset like +3d, +2w, +1m or next week";
+pub const SORT_PARSE_ERR: &str = "invalid sort: use one of text, status, created, due or priority";
+pub const DUE_FILTER_PARSE_ERR: &str =
+    "invalid date filter: use one of overdue, today or week, or a date compared like <=fri or >=-7d";
+pub const TIME_FORMAT_PARSE_ERR: &str = "invalid times: use relative or absolute";
+pub const DUE_CHANGED: &str = "Due Date Changed";
+
+pub const TAG_PARSE_ERR: &str = "invalid tags: use +tag to add a tag and -tag to remove it";
+pub const TAGS_CHANGED: &str = "Tags Changed";
+pub const NO_TAGS_TO_DISPLAY: &str = "No Tags to Display!";
+
+pub const LIST_NOT_FOUND_ERR: &str = "list not found (it can be created with `todo lists new`)";
+pub const LIST_EXISTS_ERR: &str = "a list with this name already exists";
+pub const LIST_CREATED: &str = "List Created";
+pub const LIST_RENAMED: &str = "List Renamed";
+pub const LIST_DEL_ASK: &str =
+    "Do you want to delete this list along with all of its tasks (y/N): ";
+pub const LIST_DELETED: &str = "List Deleted";
+pub const TASK_MOVED: &str = "Task(s) Moved";
+
+pub const TASK_NOT_FOUND_ERR: &str = "task not found";
+pub const TASK_PATH_PARSE_ERR: &str =
+    "invalid task: use its position like 3 (or 3.2 for a subtask), or #ID to select it by its ID";
+pub const SUBTASKS_INCOMPLETE: &str =
+    "Task(s) with incomplete subtasks left unchanged (use --cascade to also mark their subtasks)";
+
+pub const SELECTOR_PARSE_ERR: &str = "invalid selector";
+pub const SELECTOR_EXPECTED_TASK: &str =
+    "expected tasks like 3, 3.2, 1-5, 5-, -2, last, #42, all, done, open, @view or /regex/";
+pub const SELECTOR_EXPECTED_VIEW: &str = "expected the name of a view";
+pub const SELECTOR_EXPECTED_NUMBER: &str = "expected a number";
+pub const SELECTOR_EXPECTED_COMMA: &str = "expected a comma before the next tasks (without spaces)";
+pub const SELECTOR_ZERO_ERR: &str = "tasks are numbered from 1";
+pub const SELECTOR_REVERSED_ERR: &str = "the range ends before it starts";
+pub const SELECTOR_UNCLOSED_REGEX: &str = "the regex is not closed with a /";
+pub const SELECTOR_REGEX_ERR: &str = "invalid regex";
+pub const REGEX_ERR: &str = "invalid regex";
+pub const NO_MATCHES: &str = "No Matching Tasks!";
+
+pub const VIEWS_PARSE_ERR: &str =
+    "invalid views file: every line must be like \"today = open due:<=today sort:priority\"";
+pub const QUERY_PARSE_ERR: &str =
+    "invalid view query: use open, done, +tag, -tag, due:<=today (or overdue, today, week), created:>=-7d, completed:today, sort:priority, reverse and limit:5 separated by spaces";
+pub const VIEW_NOT_FOUND_ERR: &str = "view not found (views are defined in the views file)";
+pub const NO_VIEWS: &str = "No Views to Display!";
+pub const VIEWS_LOAD_ERR: &str = "Warning: could not load the views";
+
+pub const SELECTOR_WARNING: &str = "Warning";
+pub const OUT_OF_RANGE_WARN: &str = "is not in the list";
+pub const PARTLY_OUT_OF_RANGE_WARN: &str =
+    "is only partly in the list (ranges are only used when all of their tasks are there)";
+pub const UNKNOWN_ID_WARN: &str = "is not the ID of any task";
+pub const DUPLICATE_WARN: &str =
+    "selects tasks which were already selected (they are only changed once)";
+pub const STRICT_SELECTOR_ERR: &str = "tasks left unchanged because of --strict";
+
+pub const EDITOR_ERR: &str = "could not edit the tasks in the editor (it can be set with $EDITOR)";
+pub const TASK_EDITED: &str = "Task Edited";
+pub const TASK_UNCHANGED: &str = "Task Left Unchanged";
+
+pub const UNDONE: &str = "Undone";
+pub const REDONE: &str = "Redone";
+pub const NOTHING_TO_UNDO: &str = "Nothing to Undo!";
+pub const NOTHING_TO_REDO: &str = "Nothing to Redo!";
+pub const NO_HISTORY: &str = "No History to Display!";
+pub const JOURNAL_LOAD_ERR: &str = "Warning: could not load the undo history, starting a new one";
+
+pub const INVALID_TASK_FILE_ERR: &str = "invalid task file";
+pub const NEWER_SCHEMA_ERR: &str =
+    "the task file was saved by a newer version of todo, update todo to use it";
+
+pub const LEGACY_PARSE_ERR: &str =
+    "invalid task in the old text format: every line must be like \"Task text`Complete\"";
+pub const LEGACY_MIGRATE_ASK: &str =
+    "Tasks saved by an older version of todo were found, convert them to the new format (y/N): ";
+pub const LEGACY_NOT_MIGRATED: &str = "Tasks Left in the Old Format";
+pub const IMPORT_ERR: &str = "could not read the file to import";
+pub const EXPORT_ERR: &str = "could not write the exported tasks";
+pub const TASKS_EXPORTED: &str = "Task(s) Exported";
+pub const TASKS_IMPORTED: &str = "Task(s) Imported";
+pub const IMPORT_DRY_RUN: &str = "Dry Run, Task(s) Not Imported";
+pub const FORMAT_PARSE_ERR: &str =
+    "invalid format: use one of legacy, todotxt, markdown, ics, csv or tsv";
+pub const CALENDAR_PARSE_ERR: &str =
+    "invalid iCalendar file: every line must be a property like \"SUMMARY:Task text\"";
+pub const COLUMN_PARSE_ERR: &str =
+    "invalid column: use index, id, text, status, priority, due, tags, contexts, extensions, created, completed, created_at or completed_at";
+pub const MISSING_TEXT_COLUMN_ERR: &str =
+    "the file has no column with the text of the tasks (a column named text, task or title)";
+pub const CSV_PARSE_ERR: &str = "invalid value in the column";
+pub const UNKNOWN_FORMAT_ERR: &str =
+    "could not tell the format of the file from its name (it can be given with --format)";
+pub const NO_FORMAT_ERR: &str =
+    "a format must be given with --format to write the tasks to the terminal";
+pub const EXPORT_LEGACY_ERR: &str = "tasks can't be exported in the text format of older versions";
+pub const CHECKLIST_SYNCED: &str = "Checklist Updated";
+
+pub const BACKEND_PARSE_ERR: &str = "invalid storage: use one of json, sqlite or todotxt";
+pub const STORAGE_ERR: &str = "could not access the saved tasks";
+pub const LEGACY_TASK_FILE_ERR: &str = "the task file is in the text format of older versions";
+
+pub const LOCK_TIMEOUT_ERR: &str =
+    "the task file is being used by another todo command (set RTODO_LOCK_TIMEOUT to wait longer)";
+pub const LOCK_ERR: &str = "could not lock the task file";
+
+pub const OUTPUT_PARSE_ERR: &str = "invalid output: use one of text, json or ndjson";
+
 pub const NO_TASKS_TO_DISPLAY: &str = "No Tasks to Display!";
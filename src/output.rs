@@ -0,0 +1,151 @@
+// Writing the tasks and what commands did as JSON, for scripts and editor plugins
+
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+use crate::search::SearchMatch;
+use crate::{Error, Task};
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum OutputFormat {
+    // Colored text for people
+    #[default]
+    Text,
+    // A single JSON value
+    Json,
+    // A JSON value on every line, lists of values have every value on its own line
+    Ndjson,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
+            _ => Err(Error::ParseOutputError),
+        }
+    }
+}
+
+// A task with all of its fields (apart from its subtasks, which have their own records)
+// along with the list it is in, its position like "3.2" and the ID of its parent task
+pub fn task_record(list: &str, index: &str, parent: Option<u64>, task: &Task) -> Value {
+    json!({
+        "list": list,
+        "index": index,
+        "id": task.id,
+        "parent": parent,
+        "text": task.text,
+        "status": task.status,
+        "priority": task.priority,
+        "due": task.due,
+        "tags": task.tags,
+        "contexts": task.contexts,
+        "extensions": task.extensions,
+        "created": task.created,
+        "completed": task.completed,
+        "created_at": task.created_at,
+        "completed_at": task.completed_at,
+        "subtasks": task.subtasks.len(),
+    })
+}
+
+// The records of the tasks along with all of their subtasks (after their parent), given with
+// their 0-indexed positions in the list like the ones from crate::list_tasks()
+pub fn task_records(list: &str, listed: &[(usize, &Task)]) -> Vec<Value> {
+    fn add_records(
+        list: &str,
+        index: String,
+        parent: Option<u64>,
+        task: &Task,
+        records: &mut Vec<Value>,
+    ) {
+        records.push(task_record(list, &index, parent, task));
+        for (i, subtask) in task.subtasks.iter().enumerate() {
+            let index = format!("{index}.{}", i + 1);
+            add_records(list, index, Some(task.id), subtask, records);
+        }
+    }
+
+    let mut records = Vec::new();
+    for (i, task) in listed {
+        add_records(list, (i + 1).to_string(), None, task, &mut records);
+    }
+    records
+}
+
+// The records of the tasks (or subtasks) in the list with one of the IDs
+pub fn records_with_ids(list: &str, tasks: &[Task], ids: &[u64]) -> Vec<Value> {
+    let listed: Vec<(usize, &Task)> = tasks.iter().enumerate().collect();
+    task_records(list, &listed)
+        .into_iter()
+        .filter(|record| record["id"].as_u64().is_some_and(|id| ids.contains(&id)))
+        .collect()
+}
+
+// The records of the tasks found by a search, along with the parts of their text which matched
+// as ranges of characters (not bytes)
+pub fn match_records(list: &str, matches: &[SearchMatch]) -> Vec<Value> {
+    let chars = |text: &str, byte: usize| text[..byte].chars().count();
+    let records = matches.iter().map(|found| {
+        let mut record = task_record(list, &found.index, found.parent, found.task);
+        let text = &found.task.text;
+        let spans = found
+            .spans
+            .iter()
+            .map(|span| json!({ "start": chars(text, span.start), "end": chars(text, span.end) }));
+        record["matches"] = Value::Array(spans.collect());
+        record
+    });
+    records.collect()
+}
+
+pub fn print_value(format: OutputFormat, value: &Value) {
+    match format {
+        OutputFormat::Json => println!("{value:#}"),
+        _ => println!("{value}"),
+    }
+}
+
+// Writes the values as a JSON array, or with every value on its own line for NDJSON
+pub fn print_values(format: OutputFormat, values: Vec<Value>) {
+    match format {
+        OutputFormat::Ndjson => values.iter().for_each(|value| println!("{value}")),
+        _ => print_value(format, &Value::Array(values)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_of_tasks() {
+        let mut tasks = Vec::new();
+        crate::add_task(&mut tasks, "Task 1");
+        crate::add_task(&mut tasks, "Task 2 +work");
+        crate::add_subtask(&mut tasks, &[1], "Task 2.1").unwrap();
+
+        let listed = vec![(1, &tasks[1])];
+        let records = task_records("work", &listed);
+        assert_eq!(
+            records[0],
+            json!({
+                "list": "work", "index": "2", "id": 2, "parent": null, "text": "Task 2",
+                "status": "Incomplete", "priority": null, "due": null, "tags": ["work"],
+                "contexts": [], "extensions": [], "created": "2023-09-20",
+                "completed": null, "created_at": crate::clock::tests::time(), "completed_at": null,
+                "subtasks": 1,
+            })
+        );
+        assert_eq!(records[1]["index"], "2.1");
+        assert_eq!(records[1]["parent"], 2);
+
+        let records = records_with_ids("work", &tasks, &[3, 1]);
+        let indexes: Vec<&Value> = records.iter().map(|record| &record["index"]).collect();
+        assert_eq!(indexes, vec!["1", "2.1"]);
+    }
+}
@@ -0,0 +1,199 @@
+// Versions of the task file and the migrations which upgrade older files when loading them
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::{self, Write};
+
+use crate::lists::{TaskLists, DEFAULT_LIST};
+use crate::Error;
+
+// The version of the task file saved by this version of todo, it has to be increased (along
+// with adding a migration) whenever the saved data changes, so that older versions of todo
+// refuse to load the file instead of silently dropping the new data
+pub const SCHEMA_VERSION: u64 = 6;
+
+// The text format used before JSON, which is read by crate::legacy instead
+pub const LEGACY_VERSION: u64 = 0;
+
+// Upgrades the data from the version at the same index (+ 1) to the next version
+const MIGRATIONS: [fn(Value) -> Value; 5] = [
+    from_tasks_array,
+    add_schema_version,
+    add_todotxt_fields,
+    add_task_times,
+    add_next_id,
+];
+
+// The saved document, which is the lists along with the schema version
+#[derive(Serialize)]
+struct Document<'a> {
+    schema_version: u64,
+    #[serde(flatten)]
+    lists: &'a TaskLists,
+}
+
+// Files saved before versions existed are either:
+// 1 - a single array of tasks (saved by todo 3.0 and older)
+// 2 - an object with all the named lists
+fn detect_version(data: &Value) -> Result<u64, Error> {
+    match data {
+        Value::Array(_) => Ok(1),
+        Value::Object(object) => match object.get("schema_version") {
+            Some(version) => version
+                .as_u64()
+                .ok_or_else(|| Error::InvalidTaskFile("invalid schema_version".to_string())),
+            None => Ok(2),
+        },
+        _ => Err(Error::InvalidTaskFile(
+            "expected an object or an array".to_string(),
+        )),
+    }
+}
+
+// 1 -> 2: the tasks become the default list
+fn from_tasks_array(data: Value) -> Value {
+    json!({ "lists": { DEFAULT_LIST: data } })
+}
+
+// 2 -> 3: the lists are kept in a document with the schema version
+fn add_schema_version(mut data: Value) -> Value {
+    data["schema_version"] = json!(3);
+    data
+}
+
+// 3 -> 4: tasks can have the contexts, extensions and dates of todo.txt, which are optional
+fn add_todotxt_fields(mut data: Value) -> Value {
+    data["schema_version"] = json!(4);
+    data
+}
+
+// 4 -> 5: tasks can have the times they were added and completed at, which are optional
+fn add_task_times(mut data: Value) -> Value {
+    data["schema_version"] = json!(5);
+    data
+}
+
+// 5 -> 6: the next ID is saved so that the IDs of removed tasks are never used again,
+// it starts after the highest ID of the tasks (and subtasks)
+fn add_next_id(mut data: Value) -> Value {
+    fn max_id(tasks: &Value) -> u64 {
+        let tasks = tasks.as_array().into_iter().flatten();
+        let ids = tasks.map(|task| max_id(&task["subtasks"]).max(task["id"].as_u64().unwrap_or(0)));
+        ids.max().unwrap_or(0)
+    }
+
+    let lists = data["lists"]
+        .as_object()
+        .into_iter()
+        .flat_map(|lists| lists.values());
+    let next_id = lists.map(max_id).max().unwrap_or(0) + 1;
+    data["next_id"] = json!(next_id);
+    data["schema_version"] = json!(6);
+    data
+}
+
+// Loads the task lists from the JSON of a task file of any version up to SCHEMA_VERSION,
+// also returns the version of the file so that upgraded files can be saved again
+pub fn load(json: &str) -> Result<(TaskLists, u64), Error> {
+    let invalid = |e: serde_json::Error| Error::InvalidTaskFile(e.to_string());
+    let mut data: Value = serde_json::from_str(json).map_err(invalid)?;
+
+    let version = detect_version(&data)?;
+    if version == LEGACY_VERSION {
+        return Err(Error::InvalidTaskFile("invalid schema_version".to_string()));
+    }
+    if version > SCHEMA_VERSION {
+        return Err(Error::NewerSchema(version));
+    }
+    for migrate in &MIGRATIONS[(version - 1) as usize..] {
+        data = migrate(data);
+    }
+
+    let lists = serde_json::from_value(data).map_err(invalid)?;
+    Ok((lists, version))
+}
+
+// Saves the task lists as JSON with the current schema version
+pub fn save(lists: &TaskLists, writer: &mut dyn Write) -> io::Result<()> {
+    let document = Document {
+        schema_version: SCHEMA_VERSION,
+        lists,
+    };
+    serde_json::to_writer(writer, &document)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(lists: &TaskLists, name: &str) -> Vec<String> {
+        let tasks = lists.get(name).unwrap();
+        tasks.iter().map(|task| task.text().to_string()).collect()
+    }
+
+    fn saved(lists: &TaskLists) -> String {
+        let mut json = Vec::new();
+        save(lists, &mut json).unwrap();
+        String::from_utf8(json).unwrap()
+    }
+
+    #[test]
+    fn migrating_tasks_array() {
+        let (lists, version) = load(r#"[{"text":"Old","status":"Complete"}]"#).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(texts(&lists, DEFAULT_LIST), vec!["Old"]);
+        assert!(lists.get(DEFAULT_LIST).unwrap()[0].is_complete());
+    }
+
+    #[test]
+    fn migrating_unversioned_lists() {
+        let json = r#"{"lists":{"work":[{"id":1,"text":"Task 1","status":"Incomplete"}]}}"#;
+        let (lists, version) = load(json).unwrap();
+        assert_eq!(version, 2);
+        assert_eq!(texts(&lists, "work"), vec!["Task 1"]);
+    }
+
+    #[test]
+    fn saving_with_schema_version() {
+        let mut lists = TaskLists::default();
+        lists.add_task(DEFAULT_LIST, "Task 1").unwrap();
+        let json = saved(&lists);
+
+        let data: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(data["schema_version"], json!(SCHEMA_VERSION));
+        assert_eq!(load(&json).unwrap(), (lists, SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn migrating_next_id() {
+        let json = r#"{"schema_version":5,"lists":{"work":[
+            {"id":2,"text":"Task 2","status":"Incomplete","subtasks":[
+                {"id":7,"text":"Task 2.1","status":"Incomplete"}
+            ]},
+            {"id":3,"text":"Task 3","status":"Incomplete"}
+        ]}}"#;
+        let (lists, version) = load(json).unwrap();
+        assert_eq!(version, 5);
+        assert_eq!(lists.next_id(), 8);
+
+        let data: Value = serde_json::from_str(&saved(&lists)).unwrap();
+        assert_eq!(data["next_id"], json!(8));
+    }
+
+    #[test]
+    fn refusing_newer_versions() {
+        let json = r#"{"schema_version":99,"lists":{},"new_field":true}"#;
+        assert_eq!(load(json), Err(Error::NewerSchema(99)));
+    }
+
+    #[test]
+    fn invalid_task_files() {
+        assert!(matches!(load("[{"), Err(Error::InvalidTaskFile(_))));
+        assert!(matches!(load("42"), Err(Error::InvalidTaskFile(_))));
+        assert!(matches!(
+            load(r#"{"schema_version":"3"}"#),
+            Err(Error::InvalidTaskFile(_))
+        ));
+    }
+}
@@ -0,0 +1,198 @@
+// Searching the text of tasks by substrings, regexes or fuzzy queries
+
+use std::ops::Range;
+
+use regex::Regex;
+
+use crate::{Error, Task};
+
+// How a query matches the text of tasks
+#[derive(Debug)]
+pub enum Matcher {
+    // The query anywhere in the text, ignoring case
+    Substring(String),
+    Regex(Regex),
+    // The characters of the query in the same order (but not necessarily next to each other),
+    // ignoring case
+    Fuzzy(String),
+}
+
+// A task matching a search, with its position like "3.2", the ID of its parent task
+// and the byte ranges of the text which matched
+#[derive(Debug)]
+pub struct SearchMatch<'a> {
+    pub index: String,
+    pub parent: Option<u64>,
+    pub task: &'a Task,
+    pub spans: Vec<Range<usize>>,
+}
+
+fn eq_ignore_case(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+impl Matcher {
+    pub fn substring(query: &str) -> Self {
+        Matcher::Substring(query.to_string())
+    }
+
+    pub fn regex(query: &str) -> Result<Self, Error> {
+        Regex::new(query)
+            .map(Matcher::Regex)
+            .map_err(|e| Error::RegexError(e.to_string()))
+    }
+
+    pub fn fuzzy(query: &str) -> Self {
+        Matcher::Fuzzy(query.chars().filter(|c| !c.is_whitespace()).collect())
+    }
+
+    // Returns the byte ranges of the text which matched, or None if the text doesn't match
+    // (an empty query matches every text without any ranges)
+    pub fn find(&self, text: &str) -> Option<Vec<Range<usize>>> {
+        let spans = match self {
+            Matcher::Substring(query) if query.is_empty() => return Some(Vec::new()),
+            Matcher::Substring(query) => find_substrings(text, query),
+            Matcher::Regex(regex) => regex
+                .find_iter(text)
+                .map(|found| found.range())
+                .filter(|span| !span.is_empty())
+                .collect(),
+            Matcher::Fuzzy(query) if query.is_empty() => return Some(Vec::new()),
+            Matcher::Fuzzy(query) => find_fuzzy(text, query)?,
+        };
+        match self {
+            // A regex can also match an empty part of the text, like "^"
+            Matcher::Regex(regex) if regex.is_match(text) => Some(spans),
+            _ => (!spans.is_empty()).then_some(spans),
+        }
+    }
+}
+
+// The ranges of the places where the query is in the text (without overlapping each other)
+fn find_substrings(text: &str, query: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        let mut chars = text[start..].char_indices();
+        let found = query
+            .chars()
+            .all(|q| chars.next().is_some_and(|(_, c)| eq_ignore_case(c, q)));
+        if found {
+            let end = chars.next().map_or(text.len(), |(i, _)| start + i);
+            spans.push(start..end);
+            start = end;
+        } else {
+            start += text[start..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    spans
+}
+
+// The ranges of the characters of the query in the text, the first match is narrowed down by
+// looking for the query backwards from where it ends so that "td" highlights "to do" as "t" and
+// "d" instead of matching the "t" of an earlier word
+fn find_fuzzy(text: &str, query: &str) -> Option<Vec<Range<usize>>> {
+    let mut chars = text.char_indices();
+    let mut end = 0;
+    for q in query.chars() {
+        let (i, c) = chars.find(|&(_, c)| eq_ignore_case(c, q))?;
+        end = i + c.len_utf8();
+    }
+
+    let mut query = query.chars().rev().peekable();
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    for (i, c) in text[..end].char_indices().rev() {
+        let Some(&q) = query.peek() else { break };
+        if !eq_ignore_case(c, q) {
+            continue;
+        }
+        query.next();
+        // Characters next to each other are highlighted together
+        match spans.last_mut() {
+            Some(span) if span.start == i + c.len_utf8() => span.start = i,
+            _ => spans.push(i..i + c.len_utf8()),
+        }
+    }
+    spans.reverse();
+    Some(spans)
+}
+
+// Finds the tasks (and subtasks) whose text matches, in the order they are listed
+pub fn search<'a>(tasks: &'a [Task], matcher: &Matcher) -> Vec<SearchMatch<'a>> {
+    fn search_tasks<'a>(
+        tasks: &'a [Task],
+        prefix: &str,
+        parent: Option<u64>,
+        matcher: &Matcher,
+        matches: &mut Vec<SearchMatch<'a>>,
+    ) {
+        for (i, task) in tasks.iter().enumerate() {
+            let index = format!("{prefix}{}", i + 1);
+            if let Some(spans) = matcher.find(&task.text) {
+                matches.push(SearchMatch {
+                    index: index.clone(),
+                    parent,
+                    task,
+                    spans,
+                });
+            }
+            search_tasks(
+                &task.subtasks,
+                &format!("{index}."),
+                Some(task.id),
+                matcher,
+                matches,
+            );
+        }
+    }
+
+    let mut matches = Vec::new();
+    search_tasks(tasks, "", None, matcher, &mut matches);
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn finding_matches() {
+        let text = "Deploy the app, then DEPLOY the docs";
+        let substring = Matcher::substring("deploy");
+        assert_eq!(substring.find(text), Some(vec![0..6, 21..27]));
+        assert_eq!(substring.find("Write docs"), None);
+        assert_eq!(Matcher::substring("").find("Anything"), Some(vec![]));
+        assert_eq!(Matcher::substring("ÉT").find("Été"), Some(vec![0..3]));
+
+        let regex = Matcher::regex(r"\bd\w+").unwrap();
+        assert_eq!(regex.find(text), Some(vec![32..36]));
+        assert_eq!(Matcher::regex("^").unwrap().find("Task"), Some(vec![]));
+        assert!(matches!(Matcher::regex("(x"), Err(Error::RegexError(_))));
+
+        let fuzzy = Matcher::fuzzy("dpa");
+        assert_eq!(fuzzy.find(text), Some(vec![0..1, 2..3, 11..12]));
+        assert_eq!(
+            Matcher::fuzzy("td").find("the to do"),
+            Some(vec![4..5, 7..8])
+        );
+        assert_eq!(Matcher::fuzzy("ppd").find("Deploy the app"), None);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn searching_subtasks() {
+        let mut tasks = Vec::new();
+        crate::add_task(&mut tasks, "Write docs");
+        crate::add_task(&mut tasks, "Release");
+        crate::add_subtask(&mut tasks, &[1], "Update the docs").unwrap();
+
+        let matches = search(&tasks, &Matcher::substring("Docs"));
+        let found: Vec<(&str, Option<u64>)> = matches
+            .iter()
+            .map(|found| (found.index.as_str(), found.parent))
+            .collect();
+        assert_eq!(found, vec![("1", None), ("2.1", Some(2))]);
+        assert_eq!(matches[1].spans, vec![11..15]);
+    }
+}
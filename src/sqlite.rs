@@ -0,0 +1,364 @@
+// Saving the task lists in an SQLite database, where every task is a separate row so that
+// changing a few tasks doesn't need all of them to be written again
+
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::lists::TaskLists;
+use crate::storage::Storage;
+use crate::{Error, Task};
+
+// The version of the tables, saved as the user_version of the database. Like the version of
+// the JSON file (see crate::schema) it has to be increased whenever the saved tasks change
+// 1 - the first version
+// 2 - tasks can have the contexts, extensions and dates of todo.txt
+// 3 - tasks can have the times they were added and completed at
+// 4 - the next ID is saved so that the IDs of removed tasks are never used again
+const DATABASE_VERSION: u64 = 4;
+
+const CREATE_TABLES: &str = "
+CREATE TABLE lists (
+    name TEXT PRIMARY KEY
+);
+CREATE TABLE tasks (
+    id INTEGER PRIMARY KEY,
+    list TEXT NOT NULL,
+    parent INTEGER,
+    position INTEGER NOT NULL,
+    task TEXT NOT NULL
+);
+CREATE INDEX tasks_parent ON tasks (parent);
+";
+
+// The changes to the tables which upgrade the version at the index (+ 1) to the next version,
+// the tasks are saved as JSON so only new tables need changes to existing databases
+const UPGRADES: [&str; 3] = ["", "", ADD_NEXT_ID];
+
+// The next ID starts after the highest ID of the saved tasks
+const ADD_NEXT_ID: &str = "
+CREATE TABLE next_id (
+    id INTEGER NOT NULL
+);
+INSERT INTO next_id (id) SELECT COALESCE(MAX(id), 0) + 1 FROM tasks;
+";
+
+pub struct SqliteStorage {
+    connection: Connection,
+}
+
+// A task as it is saved in the tasks table, the task itself is saved as JSON without its ID
+// and subtasks (which are saved as separate rows having it as their parent)
+#[derive(PartialEq)]
+struct Row {
+    list: String,
+    parent: Option<u64>,
+    position: usize,
+    task: String,
+}
+
+fn storage_error(e: rusqlite::Error) -> Error {
+    Error::StorageError(e.to_string())
+}
+
+impl SqliteStorage {
+    // Opens the database, creating it if it doesn't exist yet
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        Self::with_connection(Connection::open(path).map_err(storage_error)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::with_connection(Connection::open_in_memory().map_err(storage_error)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, Error> {
+        let version: u64 = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(storage_error)?;
+        if version > DATABASE_VERSION {
+            return Err(Error::NewerSchema(version));
+        }
+        // New databases are created like the first version and upgraded from it
+        if version < DATABASE_VERSION {
+            let create_tables = if version == 0 { CREATE_TABLES } else { "" };
+            let upgrades = UPGRADES[version.max(1) as usize - 1..].concat();
+            connection
+                .execute_batch(&format!(
+                    "BEGIN; {create_tables} {upgrades} \
+                    PRAGMA user_version = {DATABASE_VERSION}; COMMIT;"
+                ))
+                .map_err(storage_error)?;
+        }
+        Ok(SqliteStorage { connection })
+    }
+
+    // Writes the changes between the rows of the saved and the new lists in a single
+    // transaction, when the saved lists aren't given everything is written again
+    fn write_rows(&mut self, saved: Option<&TaskLists>, new: &TaskLists) -> Result<(), Error> {
+        let (old_names, old_rows) = match saved {
+            Some(saved) => (list_names(saved), rows(saved)?),
+            None => (BTreeSet::new(), HashMap::new()),
+        };
+        let (new_names, new_rows) = (list_names(new), rows(new)?);
+
+        let transaction = self.connection.transaction().map_err(storage_error)?;
+        if saved.is_none() {
+            transaction
+                .execute_batch("DELETE FROM tasks; DELETE FROM lists;")
+                .map_err(storage_error)?;
+        }
+        for name in old_names.difference(&new_names) {
+            transaction
+                .execute("DELETE FROM lists WHERE name = ?1", [name])
+                .map_err(storage_error)?;
+        }
+        for name in new_names.difference(&old_names) {
+            transaction
+                .execute("INSERT OR REPLACE INTO lists (name) VALUES (?1)", [name])
+                .map_err(storage_error)?;
+        }
+
+        {
+            let mut delete = transaction
+                .prepare_cached("DELETE FROM tasks WHERE id = ?1")
+                .map_err(storage_error)?;
+            for id in old_rows.keys().filter(|id| !new_rows.contains_key(id)) {
+                delete.execute([id]).map_err(storage_error)?;
+            }
+
+            let mut upsert = transaction
+                .prepare_cached(
+                    "INSERT OR REPLACE INTO tasks (id, list, parent, position, task)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                )
+                .map_err(storage_error)?;
+            for (id, row) in &new_rows {
+                if old_rows.get(id) != Some(row) {
+                    upsert
+                        .execute(params![id, row.list, row.parent, row.position, row.task])
+                        .map_err(storage_error)?;
+                }
+            }
+        }
+        if saved.map(TaskLists::next_id) != Some(new.next_id()) {
+            transaction
+                .execute("UPDATE next_id SET id = ?1", [new.next_id()])
+                .map_err(storage_error)?;
+        }
+        transaction.commit().map_err(storage_error)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load(&mut self) -> Result<(TaskLists, bool), Error> {
+        let mut lists: BTreeMap<String, Vec<Task>> = BTreeMap::new();
+        let mut statement = self
+            .connection
+            .prepare("SELECT name FROM lists")
+            .map_err(storage_error)?;
+        let names = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(storage_error)?;
+        for name in names {
+            lists.insert(name.map_err(storage_error)?, Vec::new());
+        }
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, list, parent, task FROM tasks ORDER BY position")
+            .map_err(storage_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, u64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<u64>>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })
+            .map_err(storage_error)?;
+
+        // Subtasks are added to their parents after all tasks have been read
+        let mut subtasks: HashMap<u64, Vec<Task>> = HashMap::new();
+        let mut top_level = Vec::new();
+        for row in rows {
+            let (id, list, parent, task) = row.map_err(storage_error)?;
+            let mut task: Task = serde_json::from_str(&task)
+                .map_err(|e| Error::InvalidTaskFile(format!("task #{id}: {e}")))?;
+            task.id = id;
+            match parent {
+                Some(parent) => subtasks.entry(parent).or_default().push(task),
+                None => top_level.push((list, task)),
+            }
+        }
+
+        fn add_subtasks(task: &mut Task, subtasks: &mut HashMap<u64, Vec<Task>>) {
+            task.subtasks = subtasks.remove(&task.id).unwrap_or_default();
+            for subtask in &mut task.subtasks {
+                add_subtasks(subtask, subtasks);
+            }
+        }
+        for (list, mut task) in top_level {
+            add_subtasks(&mut task, &mut subtasks);
+            lists.entry(list).or_default().push(task);
+        }
+
+        let next_id = self
+            .connection
+            .query_row("SELECT id FROM next_id", [], |row| row.get(0))
+            .map_err(storage_error)?;
+        let lists: TaskLists = lists.into_iter().collect();
+        Ok((lists.with_next_id(next_id), false))
+    }
+
+    fn save(&mut self, lists: &TaskLists) -> Result<(), Error> {
+        self.write_rows(None, lists)
+    }
+
+    // Only the tasks which were added, removed, changed or moved are written
+    fn save_changes(&mut self, loaded: &TaskLists, lists: &TaskLists) -> Result<(), Error> {
+        self.write_rows(Some(loaded), lists)
+    }
+}
+
+fn list_names(lists: &TaskLists) -> BTreeSet<&str> {
+    lists.iter().map(|(name, _)| name).collect()
+}
+
+// The rows of all tasks (and subtasks) by their IDs
+fn rows(lists: &TaskLists) -> Result<HashMap<u64, Row>, Error> {
+    fn add_rows(
+        list: &str,
+        parent: Option<u64>,
+        tasks: &[Task],
+        rows: &mut HashMap<u64, Row>,
+    ) -> Result<(), Error> {
+        for (position, task) in tasks.iter().enumerate() {
+            let mut json =
+                serde_json::to_value(task).map_err(|e| Error::StorageError(e.to_string()))?;
+            if let Value::Object(object) = &mut json {
+                object.remove("id");
+                object.remove("subtasks");
+            }
+            let row = Row {
+                list: list.to_string(),
+                parent,
+                position,
+                task: json.to_string(),
+            };
+            rows.insert(task.id, row);
+            add_rows(list, Some(task.id), &task.subtasks, rows)?;
+        }
+        Ok(())
+    }
+
+    let mut rows = HashMap::new();
+    for (name, tasks) in lists.iter() {
+        add_rows(name, None, tasks, &mut rows)?;
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lists::DEFAULT_LIST;
+    use crate::{parse_selector, Priority, TaskStatus};
+
+    fn count_tasks(storage: &SqliteStorage) -> usize {
+        storage
+            .connection
+            .query_row("SELECT COUNT(*) FROM tasks", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn setup_lists() -> TaskLists {
+        let mut lists = TaskLists::default();
+        lists.create("work").unwrap();
+        lists.add_task(DEFAULT_LIST, "Task 1 +home").unwrap();
+        lists.add_task("work", "Task 2").unwrap();
+        lists.add_task("work", "Task 3").unwrap();
+        lists.add_subtask("work", &[0], "Task 2.1").unwrap();
+        lists.add_subtask("work", &[0, 0], "Task 2.1.1").unwrap();
+        lists.get_mut("work").unwrap()[1].set_priority(Some(Priority::High));
+        lists
+    }
+
+    #[test]
+    fn saving_and_loading() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        assert_eq!(storage.load(), Ok((TaskLists::default(), false)));
+
+        let lists = setup_lists();
+        storage.save(&lists).unwrap();
+        assert_eq!(storage.load(), Ok((lists, false)));
+        assert_eq!(count_tasks(&storage), 5);
+    }
+
+    #[test]
+    fn saving_changes() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        let loaded = setup_lists();
+        storage.save(&loaded).unwrap();
+
+        let mut lists = loaded.clone();
+        let tasks = lists.get_mut("work").unwrap();
+        tasks[1].set_status(TaskStatus::Complete);
+        parse_selector("1.1", tasks)
+            .unwrap()
+            .apply(tasks, crate::remove_tasks);
+        lists.create("home").unwrap();
+        lists.add_task("home", "Task 6").unwrap();
+        lists.rename("work", "office").unwrap();
+
+        storage.save_changes(&loaded, &lists).unwrap();
+        assert_eq!(storage.load(), Ok((lists, false)));
+        assert_eq!(count_tasks(&storage), 4);
+    }
+
+    #[test]
+    fn ids_are_never_reused() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        let loaded = setup_lists();
+        storage.save(&loaded).unwrap();
+
+        let mut lists = loaded.clone();
+        lists.get_mut("work").unwrap().pop();
+        storage.save_changes(&loaded, &lists).unwrap();
+        let (mut lists, _) = storage.load().unwrap();
+        assert_eq!(lists.add_task("work", "Task 6").unwrap().id(), 6);
+    }
+
+    #[test]
+    fn upgrading_databases() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(CREATE_TABLES).unwrap();
+        connection
+            .execute_batch(
+                r#"INSERT INTO lists (name) VALUES ('work');
+                INSERT INTO tasks (id, list, position, task)
+                VALUES (4, 'work', 0, '{"text":"Task 4","status":"Incomplete"}');
+                PRAGMA user_version = 3;"#,
+            )
+            .unwrap();
+
+        let mut storage = SqliteStorage::with_connection(connection).unwrap();
+        let (lists, upgraded) = storage.load().unwrap();
+        assert!(!upgraded);
+        assert_eq!(lists.get("work").unwrap()[0].id(), 4);
+        assert_eq!(lists.next_id(), 5);
+    }
+
+    #[test]
+    fn refusing_newer_databases() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch("PRAGMA user_version = 99")
+            .unwrap();
+        assert!(matches!(
+            SqliteStorage::with_connection(connection),
+            Err(Error::NewerSchema(99))
+        ));
+    }
+}
@@ -0,0 +1,396 @@
+// Loading and saving the task lists, and saving files without ever leaving them half written
+
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::lists::TaskLists;
+use crate::sqlite::SqliteStorage;
+use crate::todotxt::TodoTxtStorage;
+use crate::{legacy, schema, Error};
+
+// A place where the task lists are saved
+pub trait Storage {
+    // Loads the task lists, along with whether they were upgraded from an older version
+    // (in which case they should be saved again even when they are unchanged)
+    fn load(&mut self) -> Result<(TaskLists, bool), Error>;
+
+    // Saves the task lists, replacing all of the saved ones
+    fn save(&mut self, lists: &TaskLists) -> Result<(), Error>;
+
+    // Saves the changes made to the loaded task lists, storages which can change single
+    // tasks only write the changed ones while others save everything again
+    fn save_changes(&mut self, loaded: &TaskLists, lists: &TaskLists) -> Result<(), Error> {
+        if loaded == lists {
+            return Ok(());
+        }
+        self.save(lists)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Backend {
+    Json,
+    Sqlite,
+    TodoTxt,
+}
+
+impl Backend {
+    // Files ending with .db, .sqlite or .sqlite3 are SQLite databases, files ending with .txt
+    // are todo.txt files and anything else is JSON
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        match extension.map(str::to_lowercase).as_deref() {
+            Some("db" | "sqlite" | "sqlite3") => Backend::Sqlite,
+            Some("txt") => Backend::TodoTxt,
+            _ => Backend::Json,
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Backend::Json),
+            "sqlite" | "sqlite3" => Ok(Backend::Sqlite),
+            "todotxt" | "todo.txt" | "txt" => Ok(Backend::TodoTxt),
+            _ => Err(Error::ParseBackendError),
+        }
+    }
+}
+
+// Opens the storage for the task file at the path
+pub fn open(path: &Path, backend: Backend) -> Result<Box<dyn Storage>, Error> {
+    match backend {
+        Backend::Json => Ok(Box::new(JsonStorage::new(path))),
+        Backend::Sqlite => Ok(Box::new(SqliteStorage::open(path)?)),
+        Backend::TodoTxt => Ok(Box::new(TodoTxtStorage::new(path))),
+    }
+}
+
+// Saves all the task lists in a single JSON file which is rewritten on every change
+pub struct JsonStorage {
+    path: PathBuf,
+}
+
+impl JsonStorage {
+    pub fn new(path: &Path) -> Self {
+        JsonStorage {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl Storage for JsonStorage {
+    // A missing file is the same as having no tasks
+    fn load(&mut self) -> Result<(TaskLists, bool), Error> {
+        let data = match fs::read_to_string(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok((TaskLists::default(), false)),
+            Err(e) => return Err(Error::StorageError(e.to_string())),
+        };
+        if legacy::is_legacy(&data) {
+            return Err(Error::LegacyTaskFile);
+        }
+        let (lists, version) = schema::load(&data)?;
+        Ok((lists, version < schema::SCHEMA_VERSION))
+    }
+
+    fn save(&mut self, lists: &TaskLists) -> Result<(), Error> {
+        save_atomic(&self.path, |writer| schema::save(lists, writer))
+            .map_err(|e| Error::StorageError(e.to_string()))
+    }
+}
+
+// How often a locked file is checked again while waiting for it
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+// Returns the path with an extra extension like "todo_tasks.json.bak"
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+// The previous version of the file which is kept by save_atomic()
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+// Writes a file by first writing to a temporary file in the same directory, syncing it to disk
+// and only then renaming it over the original file, so the original is left intact if writing
+// fails at any point (the previous version is also kept as a backup)
+pub fn save_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let temp_path = with_suffix(path, &format!(".{}.tmp", process::id()));
+
+    if let Err(e) = File::create(&temp_path).and_then(|file| write_synced(file, write)) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    if path.exists() {
+        if let Err(e) = fs::copy(path, backup_path(path)) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+    }
+    fs::rename(&temp_path, path)?;
+
+    // Makes sure that the rename itself is also on the disk
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+// Appends to the file (creating it if it doesn't exist) and syncs it to disk, unlike
+// save_atomic() the end of the file may be left half written if writing fails
+pub fn append(path: &Path, write: impl FnOnce(&mut dyn Write) -> io::Result<()>) -> io::Result<()> {
+    let file = File::options().create(true).append(true).open(path)?;
+    write_synced(file, write)
+}
+
+// The journal of operations which can be undone, see crate::journal
+pub fn journal_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".journal")
+}
+
+// The views which can be used for listing and selecting tasks, see crate::views
+pub fn views_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".views")
+}
+
+// An exclusive lock on the file which is held until this is dropped
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+// The lock is taken on a separate file, since the task file itself is replaced when saving
+pub fn lock_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".lock")
+}
+
+// Locks the file so that other todo commands wait until the tasks are saved before loading
+// them, waiting for at most the timeout if the file is already locked.
+// The lock is only advisory, so it only works with other programs which also take it
+pub fn lock(path: &Path, timeout: Duration) -> Result<FileLock, Error> {
+    let lock_path = lock_path(path);
+    let lock_error = |e: io::Error| Error::LockError(format!("'{}': {e}", lock_path.display()));
+
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(lock_error)?;
+
+    let start = Instant::now();
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(FileLock { _file: file }),
+            Err(fs::TryLockError::WouldBlock) if start.elapsed() < timeout => {
+                thread::sleep(LOCK_RETRY_INTERVAL.min(timeout.saturating_sub(start.elapsed())));
+            }
+            Err(fs::TryLockError::WouldBlock) => {
+                return Err(Error::LockTimeout(path.display().to_string()))
+            }
+            Err(fs::TryLockError::Error(e)) => return Err(lock_error(e)),
+        }
+    }
+}
+
+fn write_synced(
+    file: File,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // Creates an empty directory for a test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("todo-test-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn files_in(dir: &Path) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        files
+    }
+
+    // A writer which fails after writing some of the data, like when the disk is full
+    struct FailingWriter<'a>(&'a mut dyn Write);
+
+    impl Write for FailingWriter<'_> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write_all(&buf[..buf.len() / 2])?;
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    #[test]
+    fn choosing_backend() {
+        assert_eq!(Backend::from_path(Path::new("todo.db")), Backend::Sqlite);
+        assert_eq!(
+            Backend::from_path(Path::new("a/todo.SQLite")),
+            Backend::Sqlite
+        );
+        assert_eq!(
+            Backend::from_path(Path::new("todo_tasks.json")),
+            Backend::Json
+        );
+        assert_eq!(Backend::from_path(Path::new("todo")), Backend::Json);
+        assert_eq!(Backend::from_path(Path::new("todo.txt")), Backend::TodoTxt);
+        assert_eq!("sqlite".parse(), Ok(Backend::Sqlite));
+        assert_eq!("yaml".parse::<Backend>(), Err(Error::ParseBackendError));
+    }
+
+    #[test]
+    fn json_storage() {
+        let dir = test_dir("json-storage");
+        let path = dir.join("tasks.json");
+        let mut storage = JsonStorage::new(&path);
+        assert_eq!(storage.load(), Ok((TaskLists::default(), false)));
+
+        let mut lists = TaskLists::default();
+        lists
+            .add_task(crate::lists::DEFAULT_LIST, "Task 1")
+            .unwrap();
+        storage.save_changes(&TaskLists::default(), &lists).unwrap();
+        assert_eq!(storage.load(), Ok((lists, false)));
+
+        fs::write(&path, r#"[{"text":"Old","status":"Incomplete"}]"#).unwrap();
+        assert!(storage.load().unwrap().1);
+        fs::write(&path, "Old`Incomplete").unwrap();
+        assert_eq!(storage.load(), Err(Error::LegacyTaskFile));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn locking_file() {
+        let dir = test_dir("lock");
+        let path = dir.join("tasks.json");
+        let lock_1 = lock(&path, Duration::ZERO).unwrap();
+
+        let start = Instant::now();
+        let result = lock(&path, Duration::from_millis(200));
+        assert_eq!(
+            result.unwrap_err(),
+            Error::LockTimeout(path.display().to_string())
+        );
+        assert!(start.elapsed() >= Duration::from_millis(200));
+
+        drop(lock_1);
+        assert!(lock(&path, Duration::ZERO).is_ok());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn waiting_for_lock() {
+        let dir = test_dir("lock-wait");
+        let path = dir.join("tasks.json");
+        let lock_1 = lock(&path, Duration::ZERO).unwrap();
+
+        let waiting = {
+            let path = path.clone();
+            thread::spawn(move || lock(&path, Duration::from_secs(10)).map(|_| ()))
+        };
+        thread::sleep(Duration::from_millis(100));
+        drop(lock_1);
+
+        assert_eq!(waiting.join().unwrap(), Ok(()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn saving_new_file() {
+        let dir = test_dir("save-new");
+        let path = dir.join("tasks.json");
+        save_atomic(&path, |w| w.write_all(b"[1]")).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[1]");
+        assert_eq!(files_in(&dir), vec!["tasks.json"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn saving_keeps_backup() {
+        let dir = test_dir("save-backup");
+        let path = dir.join("tasks.json");
+        save_atomic(&path, |w| w.write_all(b"[1]")).unwrap();
+        save_atomic(&path, |w| w.write_all(b"[2]")).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[2]");
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "[1]");
+        assert_eq!(files_in(&dir), vec!["tasks.json", "tasks.json.bak"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failing_writer_keeps_original() {
+        let dir = test_dir("save-failing");
+        let path = dir.join("tasks.json");
+        save_atomic(&path, |w| w.write_all(b"[1, 2, 3]")).unwrap();
+
+        let result = save_atomic(&path, |w| {
+            FailingWriter(w).write_all(b"[4, 5, 6]")?;
+            Ok(())
+        });
+
+        assert_eq!(result.unwrap_err().to_string(), "disk full");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[1, 2, 3]");
+        assert_eq!(files_in(&dir), vec!["tasks.json"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failing_serialization_keeps_original() {
+        let dir = test_dir("save-serialize");
+        let path = dir.join("tasks.json");
+        save_atomic(&path, |w| w.write_all(b"[1]")).unwrap();
+
+        let result = save_atomic(&path, |w| {
+            w.write_all(b"[")?;
+            Err(io::Error::new(io::ErrorKind::InvalidData, "bad data"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[1]");
+        assert_eq!(files_in(&dir), vec!["tasks.json"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+// Parsing of +tag tokens and filtering of tasks by their tags
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Error, Task};
+
+// Returns the tag name if the word is a tag like "+work"
+// (a "+" followed only by digits like "+1" is not treated as a tag)
+pub(crate) fn as_tag(word: &str) -> Option<&str> {
+    let tag = word.strip_prefix('+')?;
+    if tag.is_empty() || tag.chars().all(|c| c.is_ascii_digit()) {
+        None
+    } else {
+        Some(tag)
+    }
+}
+
+// Splits the +tag tokens out of a task text, returning the remaining text and the tags
+pub fn extract_tags(text: &str) -> (String, BTreeSet<String>) {
+    let mut tags = BTreeSet::new();
+    let mut words = Vec::new();
+
+    for word in text.split_whitespace() {
+        match as_tag(word) {
+            Some(tag) => {
+                tags.insert(tag.to_string());
+            }
+            None => words.push(word),
+        }
+    }
+
+    // A text without tags or made only of tags is kept as it is
+    if words.is_empty() || tags.is_empty() {
+        (text.to_string(), BTreeSet::new())
+    } else {
+        (words.join(" "), tags)
+    }
+}
+
+// Tags which a task must have and tags which it must not have
+#[derive(Debug, Default, PartialEq)]
+pub struct TagFilter {
+    pub with: BTreeSet<String>,
+    pub without: BTreeSet<String>,
+}
+
+impl TagFilter {
+    // Parses filters like ["+work", "-personal"], a tag without a sign is the same as "+tag"
+    pub fn parse(filters: &[String]) -> Result<Self, Error> {
+        let mut tag_filter = TagFilter::default();
+
+        for filter in filters {
+            let (set, tag) = match filter.strip_prefix('-') {
+                Some(tag) => (&mut tag_filter.without, tag),
+                None => (&mut tag_filter.with, filter.trim_start_matches('+')),
+            };
+            if tag.is_empty() {
+                return Err(Error::ParseTagError);
+            }
+            set.insert(tag.to_string());
+        }
+        Ok(tag_filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.with.is_empty() && self.without.is_empty()
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        self.with.iter().all(|tag| task.tags().contains(tag))
+            && !self.without.iter().any(|tag| task.tags().contains(tag))
+    }
+
+    // Whether the task or any of its subtasks (at any depth) matches, so that the tasks
+    // whose subtasks have a tag are also listed. A filter which only leaves out tags is
+    // only matched against the task itself
+    pub fn matches_with_subtasks(&self, task: &Task) -> bool {
+        self.matches(task)
+            || (!self.with.is_empty()
+                && task
+                    .subtasks()
+                    .iter()
+                    .any(|subtask| self.matches_with_subtasks(subtask)))
+    }
+}
+
+// Counts the incomplete and complete tasks (including subtasks) for every tag
+pub fn tag_counts(tasks: &[Task]) -> BTreeMap<&str, (usize, usize)> {
+    fn count<'a>(tasks: &'a [Task], counts: &mut BTreeMap<&'a str, (usize, usize)>) {
+        for task in tasks {
+            for tag in task.tags() {
+                let (open, done) = counts.entry(tag.as_str()).or_insert((0, 0));
+                if task.is_complete() {
+                    *done += 1;
+                } else {
+                    *open += 1;
+                }
+            }
+            count(task.subtasks(), counts);
+        }
+    }
+
+    let mut counts = BTreeMap::new();
+    count(tasks, &mut counts);
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TaskStatus;
+
+    fn set(tags: &[&str]) -> BTreeSet<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn extracting_tags() {
+        assert_eq!(
+            extract_tags("Fix +work the build +ci"),
+            ("Fix the build".to_string(), set(&["work", "ci"]))
+        );
+        assert_eq!(
+            extract_tags("Vote +1 on the PR"),
+            ("Vote +1 on the PR".to_string(), set(&[]))
+        );
+        assert_eq!(extract_tags("+work"), ("+work".to_string(), set(&[])));
+    }
+
+    #[test]
+    fn parsing_tag_filter() {
+        let filter = TagFilter::parse(&["+work".into(), "-personal".into(), "ci".into()]);
+        assert_eq!(
+            filter,
+            Ok(TagFilter {
+                with: set(&["work", "ci"]),
+                without: set(&["personal"]),
+            })
+        );
+        assert_eq!(TagFilter::parse(&["-".into()]), Err(Error::ParseTagError));
+    }
+
+    #[test]
+    fn filtering_by_tags() {
+        let mut tasks = Vec::new();
+        crate::add_task(&mut tasks, "Task 1 +work");
+        crate::add_task(&mut tasks, "Task 2 +work +personal");
+        crate::add_task(&mut tasks, "Task 3 +personal");
+
+        let filter = TagFilter::parse(&["+work".into(), "-personal".into()]).unwrap();
+        let matching: Vec<&str> = tasks
+            .iter()
+            .filter(|task| filter.matches(task))
+            .map(|task| task.text())
+            .collect();
+        assert_eq!(matching, vec!["Task 1"]);
+    }
+
+    #[test]
+    fn counting_tags() {
+        let mut tasks = Vec::new();
+        crate::add_task(&mut tasks, "Task 1 +work");
+        crate::add_task(&mut tasks, "Task 2 +work +personal");
+        crate::add_task(&mut tasks, "Task 3");
+        tasks[1].set_status(TaskStatus::Complete);
+
+        let counts: Vec<(&str, (usize, usize))> = tag_counts(&tasks).into_iter().collect();
+        assert_eq!(counts, vec![("personal", (0, 1)), ("work", (1, 1))]);
+    }
+
+    #[test]
+    fn tags_of_subtasks() {
+        let mut tasks = Vec::new();
+        crate::add_task(&mut tasks, "Task 1 +work");
+        crate::add_subtask(&mut tasks, &[0], "Task 2 +work").unwrap();
+        crate::add_subtask(&mut tasks, &[0, 0], "Task 3 +urgent").unwrap();
+        crate::add_task(&mut tasks, "Task 4 +personal");
+        tasks[0].subtasks[0].subtasks[0].set_status(TaskStatus::Complete);
+
+        let counts: Vec<(&str, (usize, usize))> = tag_counts(&tasks).into_iter().collect();
+        assert_eq!(
+            counts,
+            vec![("personal", (1, 0)), ("urgent", (0, 1)), ("work", (2, 0))]
+        );
+
+        let listed = |filters: &[&str]| -> Vec<&str> {
+            let filters: Vec<String> = filters.iter().map(|f| f.to_string()).collect();
+            let filter = TagFilter::parse(&filters).unwrap();
+            tasks
+                .iter()
+                .filter(|task| filter.matches_with_subtasks(task))
+                .map(|task| task.text())
+                .collect()
+        };
+        assert_eq!(listed(&["+urgent"]), vec!["Task 1"]);
+        assert_eq!(listed(&["-work"]), vec!["Task 4"]);
+    }
+}
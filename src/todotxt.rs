@@ -0,0 +1,556 @@
+// Reading and writing tasks in the todo.txt format, where every line is a task like
+// "x 2026-10-01 2026-09-20 (A) Call mom +family @phone due:2026-10-20"
+
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::lists::{TaskLists, DEFAULT_LIST};
+use crate::storage::{self, Storage};
+use crate::{legacy, tags, Error, Priority, Task, TaskStatus};
+
+// Extensions which are mapped to the fields of a task, any other extension is kept as it is
+const DUE_KEY: &str = "due";
+const PRIORITY_KEY: &str = "pri";
+const LIST_KEY: &str = "list";
+// The ID of the task and the ID of its parent task, since todo.txt doesn't have subtasks
+const ID_KEY: &str = "id";
+const PARENT_KEY: &str = "parent";
+// The line which keeps the ID for the next task in the task file, when it isn't the one after
+// the highest ID (because the task with the highest ID was removed)
+const NEXT_ID_LINE: &str = "# id-next:";
+
+// Text starting with a word which would be read as the completion mark, the priority or a date
+// is written with a backslash before that word
+const ESCAPE: char = '\\';
+
+fn parse_date(word: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()
+}
+
+// Returns the letter of a priority like "(A)"
+fn parse_priority(word: &str) -> Option<char> {
+    let letter = word.strip_prefix('(')?.strip_suffix(')')?;
+    as_priority_letter(letter)
+}
+
+fn as_priority_letter(letter: &str) -> Option<char> {
+    let mut chars = letter.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) if letter.is_ascii_uppercase() => Some(letter),
+        _ => None,
+    }
+}
+
+// Splits an extension like "key:value", where neither side is empty or has a colon in it
+// (so that links like https://example.com and times like 10:30 are not extensions)
+fn as_extension(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once(':')?;
+    let starts_with_letter = key.starts_with(|c: char| c.is_alphabetic());
+    if !starts_with_letter || value.is_empty() || value.contains(':') || value.starts_with("//") {
+        None
+    } else {
+        Some((key, value))
+    }
+}
+
+// Whether the word (without the backslashes before it) would be read as the completion mark,
+// the priority or a date at the start of a line
+fn needs_escape(word: &str) -> bool {
+    let word = word.trim_start_matches(ESCAPE);
+    word == "x" || parse_priority(word).is_some() || parse_date(word).is_some()
+}
+
+fn as_context(word: &str) -> Option<&str> {
+    word.strip_prefix('@').filter(|context| !context.is_empty())
+}
+
+fn as_id(value: &str) -> Option<u64> {
+    value.parse().ok().filter(|&id| id > 0)
+}
+
+// A task parsed from a line (with the ID given by an id:ID extension), along with the list it
+// belongs to (given by a list:name extension) and the ID of its parent task (given by a
+// parent:ID extension)
+#[derive(Debug)]
+pub struct Line {
+    pub task: Task,
+    pub list: Option<String>,
+    pub parent: Option<u64>,
+}
+
+// Parses a line into a task, returns None for empty lines. The +tags and @contexts within the
+// text (before its last word) are also kept in the text, so that they stay in place
+pub fn parse_task(line: &str) -> Option<Line> {
+    let mut words = line.split_whitespace().peekable();
+    words.peek()?;
+
+    let mut task = Task::default();
+    if words.next_if_eq(&"x").is_some() {
+        task.status = TaskStatus::Complete;
+    }
+    let mut letter = words
+        .next_if(|word| parse_priority(word).is_some())
+        .and_then(parse_priority);
+
+    // Completed tasks have the completion date before the creation date
+    let first_date = words.next_if(|word| parse_date(word).is_some());
+    let first_date = first_date.and_then(parse_date);
+    if task.is_complete() && first_date.is_some() {
+        task.completed = first_date;
+        task.created = words
+            .next_if(|word| parse_date(word).is_some())
+            .and_then(parse_date);
+    } else {
+        task.created = first_date;
+    }
+
+    let words: Vec<&str> = words.collect();
+    let is_text = |word: &str| {
+        tags::as_tag(word).is_none() && as_context(word).is_none() && as_extension(word).is_none()
+    };
+    let last_text = words.iter().rposition(|word| is_text(word));
+
+    let (mut list, mut parent) = (None, None);
+    let mut text = Vec::new();
+    for (i, &word) in words.iter().enumerate() {
+        let within_text = last_text.is_some_and(|last| i < last);
+        if let Some(tag) = tags::as_tag(word) {
+            task.tags.insert(tag.to_string());
+            if within_text {
+                text.push(word);
+            }
+        } else if let Some(context) = as_context(word) {
+            task.contexts.insert(context.to_string());
+            if within_text {
+                text.push(word);
+            }
+        } else if let Some((key, value)) = as_extension(word) {
+            match key {
+                DUE_KEY if task.due.is_none() && parse_date(value).is_some() => {
+                    task.due = parse_date(value);
+                }
+                PRIORITY_KEY if letter.is_none() && as_priority_letter(value).is_some() => {
+                    letter = as_priority_letter(value);
+                }
+                LIST_KEY if list.is_none() => list = Some(value.to_string()),
+                ID_KEY if task.id == 0 && as_id(value).is_some() => {
+                    task.id = as_id(value).unwrap_or_default();
+                }
+                PARENT_KEY if parent.is_none() && as_id(value).is_some() => parent = as_id(value),
+                _ => task.extensions.push((key.to_string(), value.to_string())),
+            }
+        } else if i == 0 && word.starts_with(ESCAPE) && needs_escape(word) {
+            text.push(&word[1..]);
+        } else {
+            text.push(word);
+        }
+    }
+    task.text = text.join(" ");
+
+    // Only the priorities A, B and C are used by todo, others are kept as an extension
+    if let Some(letter) = letter {
+        match Priority::from_letter(letter) {
+            Some(priority) => task.priority = Some(priority),
+            None => {
+                let extension = (PRIORITY_KEY.to_string(), letter.to_string());
+                task.extensions.insert(0, extension);
+            }
+        }
+    }
+    Some(Line { task, list, parent })
+}
+
+// Writes a task as a line, along with the list it belongs to (if it's not the default list)
+// and the ID of its parent (if it is a subtask)
+pub fn format_task(task: &Task, list: Option<&str>, parent: Option<u64>) -> String {
+    let letter = task.priority.map(|priority| priority.letter().to_string());
+    let letter = letter.or_else(|| {
+        let mut extensions = task.extensions.iter();
+        let pri = extensions.find(|(key, _)| key == PRIORITY_KEY);
+        pri.map(|(_, letter)| letter.clone())
+    });
+
+    let mut words = Vec::new();
+    if task.is_complete() {
+        words.push("x".to_string());
+        // A creation date can't be written without the completion date before it
+        if let Some(completed) = task.completed {
+            words.push(completed.to_string());
+            words.extend(task.created.map(|created| created.to_string()));
+        }
+    } else {
+        words.extend(letter.as_ref().map(|letter| format!("({letter})")));
+        words.extend(task.created.map(|created| created.to_string()));
+    }
+
+    let first_word = task.text.split_whitespace().next();
+    if first_word.is_some_and(needs_escape) {
+        words.push(format!("{ESCAPE}{}", task.text));
+    } else if !task.text.is_empty() {
+        words.push(task.text.clone());
+    }
+    // Tags and contexts which are within the text are already written
+    let tags = task.tags.iter().map(|tag| format!("+{tag}"));
+    let contexts = task.contexts.iter().map(|context| format!("@{context}"));
+    words.extend(tags.chain(contexts).filter(|word| !task.has_word(word)));
+    words.extend(task.due.map(|due| format!("{DUE_KEY}:{due}")));
+
+    // Completed tasks keep their priority as an extension
+    if task.is_complete() {
+        words.extend(letter.map(|letter| format!("{PRIORITY_KEY}:{letter}")));
+    }
+    let extensions = task
+        .extensions
+        .iter()
+        .filter(|(key, _)| key != PRIORITY_KEY);
+    words.extend(extensions.map(|(key, value)| format!("{key}:{value}")));
+    words.extend(list.map(|list| format!("{LIST_KEY}:{list}")));
+    if task.id != 0 {
+        words.push(format!("{ID_KEY}:{}", task.id));
+    }
+    words.extend(parent.map(|parent| format!("{PARENT_KEY}:{parent}")));
+
+    words.join(" ")
+}
+
+// Parses all tasks in the todo.txt format along with the lists they belong to. Tasks with a
+// parent:ID extension are added as subtasks of the task with the ID on an earlier line (and
+// in its list), or as top level tasks when there is no such task. Tasks without an id:ID
+// extension (or with the ID of a task on an earlier line) don't have an ID yet
+pub fn parse_tasks(data: &str) -> Vec<(Task, Option<String>)> {
+    let mut tasks: Vec<Task> = Vec::new();
+    let mut lists = Vec::new();
+    // The paths of the tasks by their ID, for adding subtasks to them
+    let mut paths: HashMap<u64, Vec<usize>> = HashMap::new();
+    let lines = data.lines().filter(|line| parse_next_id(line).is_none());
+    for line in lines.filter_map(parse_task) {
+        let mut task = line.task;
+        if paths.contains_key(&task.id) {
+            task.id = 0;
+        }
+        let id = task.id;
+        let mut path = line
+            .parent
+            .and_then(|parent| paths.get(&parent))
+            .cloned()
+            .unwrap_or_default();
+        if path.is_empty() {
+            lists.push(line.list);
+        }
+        // The paths are of tasks which were already added
+        let siblings = crate::subtasks_mut(&mut tasks, &path).unwrap();
+        siblings.push(task);
+        if id != 0 {
+            path.push(siblings.len() - 1);
+            paths.insert(id, path);
+        }
+    }
+    tasks.into_iter().zip(lists).collect()
+}
+
+fn parse_next_id(line: &str) -> Option<u64> {
+    line.trim().strip_prefix(NEXT_ID_LINE)?.trim().parse().ok()
+}
+
+// Parses the tasks in the todo.txt format for importing them, the tasks don't have IDs yet
+pub fn import_tasks(data: &str) -> Vec<Task> {
+    fn clear_ids(task: &mut Task) {
+        task.id = 0;
+        task.subtasks.iter_mut().for_each(clear_ids);
+    }
+
+    let mut tasks: Vec<Task> = parse_tasks(data)
+        .into_iter()
+        .map(|(task, _)| task)
+        .collect();
+    tasks.iter_mut().for_each(clear_ids);
+    tasks
+}
+
+// Writes the tasks as lines, subtasks are written as separate lines after their parent
+// (with the ID of their parent) since todo.txt doesn't have subtasks
+pub fn format_tasks(tasks: &[Task], list: Option<&str>, out: &mut String) {
+    fn format_subtasks(tasks: &[Task], list: Option<&str>, parent: Option<u64>, out: &mut String) {
+        for task in tasks {
+            out.push_str(&format_task(task, list, parent));
+            out.push('\n');
+            format_subtasks(&task.subtasks, list, Some(task.id), out);
+        }
+    }
+
+    format_subtasks(tasks, list, None, out);
+}
+
+// Uses a todo.txt file for saving the tasks, tasks in lists other than the default list
+// have the name of their list as a list:name extension. The next ID is only saved (on the
+// first line) when it isn't the one after the highest ID in the file, so that the IDs of
+// removed tasks aren't used again
+pub struct TodoTxtStorage {
+    path: PathBuf,
+}
+
+impl TodoTxtStorage {
+    pub fn new(path: &Path) -> Self {
+        TodoTxtStorage {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl Storage for TodoTxtStorage {
+    // Tasks added to the file by other tools don't have an ID yet
+    fn load(&mut self) -> Result<(TaskLists, bool), Error> {
+        let data = match fs::read_to_string(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok((TaskLists::default(), false)),
+            Err(e) => return Err(Error::StorageError(e.to_string())),
+        };
+        if legacy::is_legacy(&data) {
+            return Err(Error::LegacyTaskFile);
+        }
+
+        let mut lists: BTreeMap<String, Vec<Task>> = BTreeMap::new();
+        for (task, list) in parse_tasks(&data) {
+            let list = list.unwrap_or_else(|| DEFAULT_LIST.to_string());
+            lists.entry(list).or_default().push(task);
+        }
+        let next_id = data.lines().find_map(parse_next_id).unwrap_or(0);
+        let lists: TaskLists = lists.into_iter().collect();
+        Ok((lists.with_next_id(next_id), false))
+    }
+
+    // Lists without any tasks are not saved
+    fn save(&mut self, lists: &TaskLists) -> Result<(), Error> {
+        let mut data = String::new();
+        if lists.next_id() > crate::next_id(lists.iter().flat_map(|(_, tasks)| tasks)) {
+            data.push_str(&format!("{NEXT_ID_LINE}{}\n", lists.next_id()));
+        }
+        for (name, tasks) in lists.iter() {
+            let list = (name != DEFAULT_LIST).then_some(name);
+            format_tasks(tasks, list, &mut data);
+        }
+        storage::save_atomic(&self.path, |writer| writer.write_all(data.as_bytes()))
+            .map_err(|e| Error::StorageError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn set(items: &[&str]) -> BTreeSet<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    fn date(date: &str) -> Option<NaiveDate> {
+        parse_date(date)
+    }
+
+    fn round_trip(line: &str) -> String {
+        let Line { task, list, parent } = parse_task(line).unwrap();
+        format_task(&task, list.as_deref(), parent)
+    }
+
+    #[test]
+    fn parsing_tasks() {
+        let line = "(A) 2026-09-20 Call mom +family @phone due:2026-10-20 rec:1w";
+        let Line { task, list, .. } = parse_task(line).unwrap();
+        assert_eq!(task.text(), "Call mom");
+        assert_eq!(task.priority(), Some(Priority::High));
+        assert_eq!(task.created, date("2026-09-20"));
+        assert_eq!(task.tags(), &set(&["family"]));
+        assert_eq!(task.contexts(), &set(&["phone"]));
+        assert_eq!(task.due(), date("2026-10-20"));
+        assert_eq!(task.extensions(), &[("rec".to_string(), "1w".to_string())]);
+        assert!(!task.is_complete());
+        assert_eq!(list, None);
+    }
+
+    #[test]
+    fn parsing_completed_tasks() {
+        let Line { task, .. } = parse_task("x 2026-10-01 2026-09-20 Pay rent pri:B").unwrap();
+        assert!(task.is_complete());
+        assert_eq!(task.completed, date("2026-10-01"));
+        assert_eq!(task.created, date("2026-09-20"));
+        assert_eq!(task.priority(), Some(Priority::Medium));
+        assert_eq!(task.text(), "Pay rent");
+
+        let Line { task, .. } = parse_task("x 2026-10-01 Pay rent").unwrap();
+        assert_eq!((task.completed, task.created), (date("2026-10-01"), None));
+    }
+
+    #[test]
+    fn parsing_special_words() {
+        let Line { task, list, .. } =
+            parse_task("Read https://example.com at 10:30 list:work (B) x @ +1").unwrap();
+        assert_eq!(task.text(), "Read https://example.com at 10:30 (B) x @ +1");
+        assert_eq!(task.priority(), None);
+        assert_eq!(list.as_deref(), Some("work"));
+        assert!(parse_task("   ").is_none());
+    }
+
+    #[test]
+    fn tasks_round_trip() {
+        for line in [
+            "(A) 2026-09-20 Call mom +family @phone due:2026-10-20",
+            "x 2026-10-01 2026-09-20 Pay rent +home pri:C",
+            "(D) Unknown priority t:2026-10-10 rec:+1w",
+            "x Done pri:E",
+            "Task in a list list:work",
+            "x 2026-10-01 Completed without creation date",
+        ] {
+            assert_eq!(round_trip(line), line);
+        }
+    }
+
+    #[test]
+    fn completing_tasks_keeps_date() {
+        let Line { mut task, .. } = parse_task("2026-09-20 Call mom").unwrap();
+        task.set_status(TaskStatus::Complete);
+        let today = crate::clock::now().date_naive();
+        assert_eq!(
+            format_task(&task, None, None),
+            format!("x {today} 2026-09-20 Call mom")
+        );
+    }
+
+    #[test]
+    fn formatting_subtasks() {
+        let mut tasks = Vec::new();
+        crate::add_task(&mut tasks, "Task 1");
+        crate::add_subtask(&mut tasks, &[0], "Task 1.1").unwrap();
+        tasks[0].status = TaskStatus::Complete;
+
+        let mut out = String::new();
+        format_tasks(&tasks, Some("work"), &mut out);
+        // Tasks which are added have the date they were added on, completed tasks
+        // without the date they were completed on can't have it though
+        assert_eq!(
+            out,
+            "x Task 1 list:work id:1\n2023-09-20 Task 1.1 list:work id:2 parent:1\n"
+        );
+    }
+
+    #[test]
+    fn tasks_round_trip_with_ids_and_subtasks() {
+        let data = "\
+            x Tasks +home pri:B id:3\n\
+            \\x marks the spot id:5 parent:3\n\
+            2026-09-20 \\(A) is not a priority id:4 parent:5\n\
+            \\2026-09-20 is not a date @desk id:6 parent:3\n\
+            Call +mom about @home the rent +money id:7\n\
+            Added by another tool\n\
+            Missing parent id:8 parent:42\n";
+        let tasks = parse_tasks(data);
+        let tree: Vec<(&str, u64, usize)> = tasks
+            .iter()
+            .map(|(task, _)| (task.text(), task.id(), task.subtasks().len()))
+            .collect();
+        assert_eq!(
+            tree,
+            vec![
+                ("Tasks", 3, 2),
+                ("Call +mom about @home the rent", 7, 0),
+                ("Added by another tool", 0, 0),
+                ("Missing parent", 8, 0),
+            ]
+        );
+        let subtasks = tasks[0].0.subtasks();
+        assert_eq!(subtasks[0].text(), "x marks the spot");
+        assert!(!subtasks[0].is_complete());
+        assert_eq!(subtasks[0].subtasks()[0].text(), "(A) is not a priority");
+        assert_eq!(subtasks[0].subtasks()[0].priority(), None);
+        assert_eq!(subtasks[1].text(), "2026-09-20 is not a date");
+        assert_eq!(subtasks[1].created, None);
+        assert_eq!(tasks[1].0.tags(), &set(&["mom", "money"]));
+        assert_eq!(tasks[1].0.contexts(), &set(&["home"]));
+        let mut task = tasks[1].0.clone();
+        task.remove_tag("mom");
+        assert_eq!(task.text(), "Call about @home the rent");
+
+        let mut out = String::new();
+        let tasks: Vec<Task> = tasks.into_iter().map(|(task, _)| task).collect();
+        format_tasks(&tasks, None, &mut out);
+        assert_eq!(out, data.replace(" parent:42", ""));
+    }
+
+    #[test]
+    fn todotxt_storage() {
+        let dir = std::env::temp_dir().join(format!("todo-test-todotxt-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("todo.txt");
+        fs::write(&path, "(A) Task 1 +work\nTask 2 list:home\nx Task 3\n").unwrap();
+
+        let mut storage = TodoTxtStorage::new(&path);
+        let (mut lists, _) = storage.load().unwrap();
+        lists.assign_missing_ids();
+        let ids: Vec<(&str, u64)> = lists
+            .iter()
+            .flat_map(|(name, tasks)| tasks.iter().map(move |task| (name, task.id())))
+            .collect();
+        assert_eq!(ids, vec![("default", 1), ("default", 2), ("home", 3)]);
+
+        storage.save(&lists).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "(A) Task 1 +work id:1\nx Task 3 id:2\nTask 2 list:home id:3\n"
+        );
+        assert_eq!(storage.load().unwrap().0, lists);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ids_are_never_reused() {
+        let dir = std::env::temp_dir().join(format!("todo-test-next-id-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("todo.txt");
+        let mut storage = TodoTxtStorage::new(&path);
+        let mut lists = TaskLists::default();
+        for text in ["a", "b", "c"] {
+            lists.add_task(DEFAULT_LIST, text).unwrap();
+        }
+        storage.save(&lists).unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains(NEXT_ID_LINE));
+
+        // Removing the task with the highest ID
+        lists.get_mut(DEFAULT_LIST).unwrap().pop();
+        storage.save(&lists).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# id-next:4\n2023-09-20 a id:1\n2023-09-20 b id:2\n"
+        );
+        let (mut lists, _) = storage.load().unwrap();
+        assert_eq!(lists.add_task(DEFAULT_LIST, "d").unwrap().id(), 4);
+        storage.save(&lists).unwrap();
+        assert_eq!(storage.load().unwrap().0.next_id(), 5);
+
+        // The line isn't imported as a task
+        assert_eq!(import_tasks("# id-next:7\ne\n").len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn detecting_legacy_files() {
+        let dir = std::env::temp_dir().join(format!("todo-test-legacy-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // The old default file ends with .txt too
+        let path = dir.join(legacy::LEGACY_TASKS_FILE);
+        let mut storage = TodoTxtStorage::new(&path);
+        fs::write(&path, "Buy milk`Incomplete\nCall mom`Complete\n").unwrap();
+        assert_eq!(storage.load(), Err(Error::LegacyTaskFile));
+
+        fs::write(&path, "(A) Rename `main`\nx 2023-09-20 Fix `cargo test`\n").unwrap();
+        let (lists, _) = storage.load().unwrap();
+        let texts: Vec<&str> = lists
+            .iter()
+            .flat_map(|(_, tasks)| tasks)
+            .map(Task::text)
+            .collect();
+        assert_eq!(texts, vec!["Rename `main`", "Fix `cargo test`"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
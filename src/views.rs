@@ -0,0 +1,145 @@
+// Named views which list the tasks matching a saved query, defined in a file with lines like
+// "today = open due:<=today sort:priority"
+
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+
+use crate::dates::DueFilter;
+use crate::tags::TagFilter;
+use crate::{Error, ListOptions, TaskStatus};
+
+// The views by their name, along with their queries
+#[derive(Debug, Default, PartialEq)]
+pub struct Views(BTreeMap<String, String>);
+
+fn is_view_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+impl Views {
+    // Parses the lines of a views file, blank lines and lines starting with # are skipped
+    // (the queries are only parsed when the views are used, so that dates like "today"
+    // are relative to when they are used)
+    pub fn parse(data: &str) -> Result<Self, Error> {
+        let mut views = BTreeMap::new();
+        for (i, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((name, query)) if is_view_name(name.trim()) => {
+                    views.insert(name.trim().to_string(), query.trim().to_string());
+                }
+                _ => return Err(Error::ParseViewsError(i + 1)),
+            }
+        }
+        Ok(Views(views))
+    }
+
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    // The names of the views along with their queries
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, query)| (name.as_str(), query.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // The options for listing the tasks of the view
+    pub fn options(&self, name: &str, today: NaiveDate) -> Result<ListOptions, Error> {
+        let query = self
+            .query(name)
+            .ok_or_else(|| Error::ViewNotFound(name.to_string()))?;
+        parse_query(query, today)
+    }
+}
+
+// Parses a query made of terms separated by spaces, which are "open", "done", tags like "+work"
+// and "-personal", "due:" followed by a filter like "today" or a comparison like "<=fri"
+// (as well as "created:" and "completed:" for the dates tasks were added and completed on),
+// "sort:" followed by a key like "priority", "reverse" and "limit:" followed by a number
+pub fn parse_query(query: &str, today: NaiveDate) -> Result<ListOptions, Error> {
+    let mut options = ListOptions::default();
+    let mut tags = Vec::new();
+    for term in query.split_whitespace() {
+        let invalid = || Error::ParseQueryError(term.to_string());
+        match term.split_once(':') {
+            Some(("due", filter)) => {
+                options.due = Some(DueFilter::parse_comparison(filter, today)?);
+            }
+            Some(("created", filter)) => {
+                options.created = Some(DueFilter::parse_comparison(filter, today)?);
+            }
+            Some(("completed", filter)) => {
+                options.completed = Some(DueFilter::parse_comparison(filter, today)?);
+            }
+            Some(("sort", key)) => options.sort = Some(key.parse()?),
+            Some(("limit", limit)) => options.limit = Some(limit.parse().map_err(|_| invalid())?),
+            Some(_) => return Err(invalid()),
+            None => match term {
+                "open" => options.status = Some(TaskStatus::Incomplete),
+                "done" => options.status = Some(TaskStatus::Complete),
+                "reverse" => options.reverse = true,
+                tag if tag.starts_with(['+', '-']) => tags.push(tag.to_string()),
+                _ => return Err(invalid()),
+            },
+        }
+    }
+    options.tags = TagFilter::parse(&tags)?;
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SortKey;
+
+    #[test]
+    fn parsing_views() {
+        let today = NaiveDate::from_ymd_opt(2023, 9, 20).unwrap();
+        let data = "# Views\n\ntoday = open due:<=today sort:priority\nwork-top = +work -later limit:3 reverse\nrecent = done completed:>=-7d\n";
+        let views = Views::parse(data).unwrap();
+        assert_eq!(
+            views.iter().collect::<Vec<_>>(),
+            vec![
+                ("recent", "done completed:>=-7d"),
+                ("today", "open due:<=today sort:priority"),
+                ("work-top", "+work -later limit:3 reverse"),
+            ]
+        );
+
+        let options = views.options("today", today).unwrap();
+        assert_eq!(options.status, Some(TaskStatus::Incomplete));
+        assert_eq!(options.due, Some(DueFilter::OnOrBefore(today)));
+        assert_eq!(options.sort, Some(SortKey::Priority));
+        let options = views.options("work-top", today).unwrap();
+        assert!(options.tags.with.contains("work") && options.tags.without.contains("later"));
+        assert_eq!((options.limit, options.reverse), (Some(3), true));
+        let options = views.options("recent", today).unwrap();
+        let week_ago = NaiveDate::from_ymd_opt(2023, 9, 13).unwrap();
+        assert_eq!(options.completed, Some(DueFilter::OnOrAfter(week_ago)));
+
+        assert_eq!(
+            views.options("tomorrow", today).unwrap_err(),
+            Error::ViewNotFound("tomorrow".to_string())
+        );
+        assert_eq!(
+            parse_query("open size:big", today).unwrap_err(),
+            Error::ParseQueryError("size:big".to_string())
+        );
+        assert_eq!(
+            Views::parse("today = open\nno name\n"),
+            Err(Error::ParseViewsError(2))
+        );
+    }
+}